@@ -1 +1,124 @@
-fn main() {}
+use std::process::ExitCode;
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use pg_ephemeral::reaper::{ReapReport, ReapedResource, Reaper};
+
+fn cli() -> Command {
+    Command::new("pg-ephemeral")
+        .about("Manage ephemeral PostgreSQL instances")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("reap")
+                .about("Remove containers and data directories left behind by dead processes")
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("Only list the stale resources"),
+                )
+                .arg(
+                    Arg::new("no-containers")
+                        .long("no-containers")
+                        .action(ArgAction::SetTrue)
+                        .help("Skip docker containers"),
+                )
+                .arg(
+                    Arg::new("reused")
+                        .long("reused")
+                        .action(ArgAction::SetTrue)
+                        .help("Also remove containers kept for reuse, even if a process uses them"),
+                )
+                .arg(
+                    Arg::new("no-data-dirs")
+                        .long("no-data-dirs")
+                        .action(ArgAction::SetTrue)
+                        .help("Skip local data directories"),
+                ),
+        )
+}
+
+fn reap(args: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let dry_run = args.get_flag("dry-run");
+    let reaper = Reaper::new()
+        .with_dry_run(dry_run)
+        .with_reused(args.get_flag("reused"));
+
+    let (mut total, mut failed) = (0, 0);
+
+    // report every step right away, a later one failing (e.g. no docker) doesn't hide it
+    if !args.get_flag("no-data-dirs") {
+        let sweep = reaper.reap_data_dirs()?;
+        total += report(&sweep, dry_run);
+        failed += sweep.failed.len();
+    }
+
+    if !args.get_flag("no-containers") {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let sweep = runtime.block_on(reaper.reap_containers())?;
+        total += report(&sweep, dry_run);
+        failed += sweep.failed.len();
+    }
+
+    if total == 0 {
+        println!("nothing to reap");
+    }
+
+    match failed {
+        0 => Ok(()),
+        failed => Err(format!("{failed} stale resources could not be removed").into()),
+    }
+}
+
+/// Prints every resource of `report`, returns how many were found
+fn report(report: &ReapReport, dry_run: bool) -> usize {
+    let action = if dry_run { "would remove" } else { "removed" };
+
+    for resource in &report.reaped {
+        println!("{action} {}", describe(resource));
+    }
+
+    for (resource, err) in &report.failed {
+        eprintln!("failed to remove {}: {err}", describe(resource));
+    }
+
+    report.reaped.len() + report.failed.len()
+}
+
+fn describe(resource: &ReapedResource) -> String {
+    match resource {
+        ReapedResource::DataDir {
+            path, owner_pid, ..
+        } => format!("data directory {} (pid {owner_pid})", path.display()),
+        ReapedResource::Container {
+            id,
+            name,
+            owner_pid,
+        } => format!(
+            "container {} (pid {owner_pid})",
+            name.as_deref().unwrap_or(id)
+        ),
+        ReapedResource::ReusedContainer { id, name } => {
+            format!("reused container {}", name.as_deref().unwrap_or(id))
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let matches = cli().get_matches();
+
+    let result = match matches.subcommand() {
+        Some(("reap", args)) => reap(args),
+        _ => unreachable!("clap requires a subcommand"),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -10,11 +10,12 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
         .with_max_level(tracing::Level::TRACE)
         .init();
 
-    let config = ContainerizedConfig::new();
+    let config = ContainerizedConfig::builder().build()?;
     let mut containerized = Containerized::new(config);
 
     containerized.start().await?;
 
+    dbg!(containerized.connection_uri());
     dbg!(containerized.is_running().await?);
 
     containerized.shutdown().await?;
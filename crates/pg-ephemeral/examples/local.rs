@@ -0,0 +1,26 @@
+use pg_ephemeral::Ephemeral;
+use pg_ephemeral::local::{Local, LocalConfig};
+
+#[tokio::main]
+async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "tracing")]
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .init();
+
+    let config = LocalConfig::builder().build()?;
+    let mut local = Local::new(config)?;
+
+    local.start().await?;
+
+    let info = local.connection_info()?;
+    dbg!(info.to_uri());
+    dbg!(info.to_keyword_value());
+    dbg!(local.is_running().await?);
+
+    local.shutdown().await?;
+
+    dbg!(local.is_running().await?);
+
+    Ok(())
+}
@@ -0,0 +1,279 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use hmac::{Hmac, Mac};
+use md5::{Digest, Md5};
+use sha2::Sha256;
+
+use super::error::{ClientError, ClientResult};
+
+pub(crate) const SCRAM_SHA_256: &str = "SCRAM-SHA-256";
+
+/// GS2 header without channel binding, `biws` is its base64 encoding
+const GS2_HEADER: &str = "n,,";
+const GS2_HEADER_BASE64: &str = "biws";
+
+/// Length of the random client nonce in bytes, before base64 encoding
+const NONCE_LEN: usize = 18;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// `md5` followed by `md5(md5(password || user) || salt)` in hex
+pub(crate) fn md5_password(user: &str, password: &str, salt: &[u8]) -> String {
+    let inner = hex(&Md5::new()
+        .chain_update(password)
+        .chain_update(user)
+        .finalize());
+
+    let outer = Md5::new().chain_update(inner).chain_update(salt).finalize();
+
+    format!("md5{}", hex(&outer))
+}
+
+/// Client side of a SCRAM-SHA-256 exchange (RFC 5802, RFC 7677), without
+/// channel binding
+pub(crate) struct ScramSha256 {
+    /// Empty, the server takes the user from the startup message
+    user: String,
+    password: String,
+    nonce: String,
+    state: ScramState,
+}
+
+enum ScramState {
+    /// `client-first-message` sent
+    Started,
+    /// `client-final-message` sent, the server signature is expected next
+    Finished { server_signature: Vec<u8> },
+}
+
+impl ScramSha256 {
+    pub fn new(password: &str) -> ClientResult<Self> {
+        let mut nonce = [0; NONCE_LEN];
+        getrandom::fill(&mut nonce)
+            .map_err(|err| ClientError::Scram(format!("failed to generate a nonce: {err}")))?;
+
+        Ok(Self::with_nonce("", password, &BASE64.encode(nonce)))
+    }
+
+    /// Exchange with a fixed user and nonce, e.g. to replay a known one
+    pub(crate) fn with_nonce(user: &str, password: &str, nonce: &str) -> Self {
+        Self {
+            user: user.into(),
+            password: password.into(),
+            nonce: nonce.into(),
+            state: ScramState::Started,
+        }
+    }
+
+    fn client_first_bare(&self) -> String {
+        format!("n={},r={}", self.user, self.nonce)
+    }
+
+    /// `client-first-message`, sent with the `SASLInitialResponse`
+    pub fn client_first(&self) -> String {
+        format!("{GS2_HEADER}{}", self.client_first_bare())
+    }
+
+    /// Answers the `server-first-message` with the `client-final-message`
+    pub fn client_final(&mut self, server_first: &str) -> ClientResult<String> {
+        let mut nonce = None;
+        let mut salt = None;
+        let mut iterations = None;
+
+        for attribute in server_first.split(',') {
+            match attribute.split_once('=') {
+                Some(("r", value)) => nonce = Some(value),
+                Some(("s", value)) => salt = Some(value),
+                Some(("i", value)) => iterations = value.parse::<u32>().ok(),
+                _ => {}
+            }
+        }
+
+        let (Some(nonce), Some(salt), Some(iterations)) = (nonce, salt, iterations) else {
+            return Err(ClientError::Scram(format!(
+                "malformed server-first-message: {server_first}"
+            )));
+        };
+
+        if !nonce.starts_with(&self.nonce) || nonce.len() == self.nonce.len() {
+            return Err(ClientError::Scram(
+                "server nonce doesn't extend ours".into(),
+            ));
+        }
+
+        let salt = BASE64
+            .decode(salt)
+            .map_err(|err| ClientError::Scram(format!("invalid salt: {err}")))?;
+
+        // PostgreSQL itself uses the raw password whenever SASLprep rejects it,
+        // ASCII passwords are unchanged by it anyway
+        let salted_password = hi(self.password.as_bytes(), &salt, iterations);
+
+        let client_key = hmac(&salted_password, b"Client Key");
+        let stored_key = Sha256::digest(client_key);
+
+        let client_final_without_proof = format!("c={GS2_HEADER_BASE64},r={nonce}");
+        let auth_message = format!(
+            "{},{server_first},{client_final_without_proof}",
+            self.client_first_bare()
+        );
+
+        let client_signature = hmac(&stored_key, auth_message.as_bytes());
+        let proof: Vec<u8> = client_key
+            .iter()
+            .zip(client_signature)
+            .map(|(key, signature)| key ^ signature)
+            .collect();
+
+        let server_key = hmac(&salted_password, b"Server Key");
+        self.state = ScramState::Finished {
+            server_signature: hmac(&server_key, auth_message.as_bytes()).to_vec(),
+        };
+
+        Ok(format!(
+            "{client_final_without_proof},p={}",
+            BASE64.encode(proof)
+        ))
+    }
+
+    /// Checks the `server-final-message`, proving the server knew the password too
+    pub fn verify(&self, server_final: &str) -> ClientResult<()> {
+        let ScramState::Finished {
+            ref server_signature,
+        } = self.state
+        else {
+            return Err(ClientError::Scram("unexpected server-final-message".into()));
+        };
+
+        if let Some(error) = server_final.strip_prefix("e=") {
+            return Err(ClientError::Scram(error.into()));
+        }
+
+        let signature = server_final
+            .strip_prefix("v=")
+            .and_then(|signature| BASE64.decode(signature).ok())
+            .ok_or_else(|| {
+                ClientError::Scram(format!("malformed server-final-message: {server_final}"))
+            })?;
+
+        if signature != *server_signature {
+            return Err(ClientError::Scram("invalid server signature".into()));
+        }
+
+        Ok(())
+    }
+}
+
+/// `Hi()` of RFC 5802, which is PBKDF2 with HMAC-SHA-256 and a single block
+fn hi(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    let mut mac = HmacSha256::new_from_slice(password).expect("HMAC accepts any key length");
+    mac.update(salt);
+    mac.update(&1u32.to_be_bytes());
+
+    let mut previous: [u8; 32] = mac.finalize().into_bytes().into();
+    let mut result = previous;
+
+    for _ in 1..iterations {
+        previous = hmac(password, &previous);
+        result
+            .iter_mut()
+            .zip(previous)
+            .for_each(|(result, byte)| *result ^= byte);
+    }
+
+    result
+}
+
+fn hmac(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The SCRAM-SHA-256 example exchange of RFC 7677, section 3
+    const RFC7677_SERVER_FIRST: &str =
+        "r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096";
+
+    #[test]
+    fn scram_sha256_matches_rfc7677() {
+        let mut scram = ScramSha256::with_nonce("user", "pencil", "rOprNGfwEbeRWgbNEkqO");
+        assert_eq!(scram.client_first(), "n,,n=user,r=rOprNGfwEbeRWgbNEkqO");
+
+        assert_eq!(
+            scram.client_final(RFC7677_SERVER_FIRST).unwrap(),
+            "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,\
+             p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ="
+        );
+        scram
+            .verify("v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=")
+            .unwrap();
+    }
+
+    #[test]
+    fn scram_sha256_rejects_a_forged_exchange() {
+        let mut scram = ScramSha256::with_nonce("user", "pencil", "rOprNGfwEbeRWgbNEkqO");
+        assert!(
+            scram
+                .verify("v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=")
+                .is_err()
+        );
+
+        // the server must extend our nonce
+        assert!(
+            scram
+                .client_final("r=rOprNGfwEbeRWgbNEkqO,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096")
+                .is_err()
+        );
+        assert!(
+            scram
+                .client_final("r=other,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096")
+                .is_err()
+        );
+        assert!(
+            scram
+                .client_final("r=rOprNGfwEbeRWgbNEkqOx,i=4096")
+                .is_err()
+        );
+
+        let mut scram = ScramSha256::with_nonce("user", "wrong", "rOprNGfwEbeRWgbNEkqO");
+        scram.client_final(RFC7677_SERVER_FIRST).unwrap();
+        assert!(
+            scram
+                .verify("v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=")
+                .is_err()
+        );
+        assert!(scram.verify("e=invalid-proof").is_err());
+    }
+
+    #[test]
+    fn scram_sha256_leaves_the_user_to_the_startup_message() {
+        let scram = ScramSha256::new("pencil").unwrap();
+        let first = scram.client_first();
+
+        assert!(first.starts_with("n,,n=,r="));
+        assert_eq!(
+            BASE64.decode(&first["n,,n=,r=".len()..]).unwrap().len(),
+            NONCE_LEN
+        );
+    }
+
+    #[test]
+    fn md5_password_hashes_user_and_salt() {
+        assert_eq!(
+            md5_password("postgres", "secret", &[1, 2, 3, 4]),
+            "md5bb41a296aab6baccb36ff243a562abff"
+        );
+        assert_ne!(
+            md5_password("postgres", "secret", &[1, 2, 3, 5]),
+            md5_password("postgres", "secret", &[1, 2, 3, 4])
+        );
+    }
+}
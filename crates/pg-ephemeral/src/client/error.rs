@@ -0,0 +1,135 @@
+use std::fmt;
+
+use crate::SslMode;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("I/O operation failed: {0}")]
+    IOError(#[from] std::io::Error),
+
+    #[error("{0}")]
+    DbError(Box<DbError>),
+
+    #[error("protocol violation: {0}")]
+    Protocol(String),
+
+    #[error("unsupported authentication method: {0}")]
+    UnsupportedAuth(String),
+
+    #[error("the server requested a password, but none is configured")]
+    PasswordRequired,
+
+    #[error("SCRAM authentication failed: {0}")]
+    Scram(String),
+
+    #[error("TLS is not supported, `sslmode={0}` can't be satisfied")]
+    TlsUnsupported(SslMode),
+}
+
+impl ClientError {
+    /// The error reported by the server, if it was one
+    pub fn as_db_error(&self) -> Option<&DbError> {
+        match self {
+            ClientError::DbError(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// `SQLSTATE` of the error reported by the server, if it was one
+    pub fn code(&self) -> Option<&str> {
+        self.as_db_error().map(|err| err.code.as_str())
+    }
+}
+
+impl From<DbError> for ClientError {
+    fn from(err: DbError) -> Self {
+        Self::DbError(Box::new(err))
+    }
+}
+
+pub type ClientResult<T> = std::result::Result<T, ClientError>;
+
+/// An `ErrorResponse` sent by the server
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DbError {
+    /// `ERROR`, `FATAL` or `PANIC`
+    pub severity: String,
+    /// `SQLSTATE` code, e.g. `42P01` for an undefined table
+    pub code: String,
+    pub message: String,
+    pub detail: Option<String>,
+    pub hint: Option<String>,
+    /// 1-based character offset into the query the error refers to
+    pub position: Option<u32>,
+}
+
+impl DbError {
+    /// 1-based line and column of [`DbError::position`] inside `sql`, the
+    /// query that failed
+    pub fn line_column(&self, sql: &str) -> Option<(usize, usize)> {
+        let position = self.position? as usize;
+        let prefix: String = sql.chars().take(position.checked_sub(1)?).collect();
+
+        let line = prefix.matches('\n').count() + 1;
+        let column = prefix
+            .rsplit('\n')
+            .next()
+            .map_or(0, |line| line.chars().count())
+            + 1;
+
+        Some((line, column))
+    }
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} ({})", self.severity, self.message, self.code)?;
+
+        if let Some(ref detail) = self.detail {
+            write!(f, "\nDETAIL: {detail}")?;
+        }
+
+        if let Some(ref hint) = self.hint {
+            write!(f, "\nHINT: {hint}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for DbError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(position: Option<u32>) -> DbError {
+        DbError {
+            position,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn line_column_counts_from_one() {
+        let sql = "SELECT 1;\nSELEC 2;\n  FROM t";
+
+        assert_eq!(at(Some(1)).line_column(sql), Some((1, 1)));
+        assert_eq!(at(Some(8)).line_column(sql), Some((1, 8)));
+        assert_eq!(at(Some(11)).line_column(sql), Some((2, 1)));
+        assert_eq!(at(Some(22)).line_column(sql), Some((3, 3)));
+    }
+
+    #[test]
+    fn line_column_counts_characters_not_bytes() {
+        // the server reports the position in characters
+        assert_eq!(at(Some(4)).line_column("'é' x"), Some((1, 4)));
+        assert_eq!(at(Some(5)).line_column("'é'\nx"), Some((2, 1)));
+    }
+
+    #[test]
+    fn line_column_needs_a_position() {
+        assert_eq!(at(None).line_column("SELECT"), None);
+        assert_eq!(at(Some(0)).line_column("SELECT"), None);
+    }
+}
@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use super::auth::{SCRAM_SHA_256, ScramSha256, md5_password};
+use super::error::{ClientError, ClientResult};
+use super::message::{BackendMessage, FrontendMessage, parse_error_fields, startup_message};
+use crate::ConnectionInfo;
+use crate::common::constants::CLIENT_CONNECT_TIMEOUT;
+use crate::common::sql::{quote_ident, quote_literal};
+use crate::{log_debug, log_warn};
+
+/// Result of a single statement of a simple query, values in text format
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Option<String>>>,
+    /// Tag of the completed command, e.g. `INSERT 0 1` or `CREATE TABLE`
+    pub command_tag: String,
+}
+
+/// Minimal synchronous client for the frontend/backend protocol.
+///
+/// It covers what talking to a freshly started server needs: the startup,
+/// cleartext, MD5 and SCRAM-SHA-256 authentication, and the simple query
+/// protocol. Values are returned in text format, TLS isn't supported.
+/// For anything else, use a real driver with [`ConnectionInfo`].
+#[derive(Debug)]
+pub struct Client {
+    stream: Stream,
+    /// `ParameterStatus` values reported by the server (`server_version`, ...)
+    parameters: HashMap<String, String>,
+    backend_pid: i32,
+    /// `Terminate` was sent, by [`Client::close`] or on drop
+    closed: bool,
+}
+
+#[derive(Debug)]
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixStream),
+}
+
+impl Client {
+    /// Connects and authenticates as [`ConnectionInfo::user`]
+    pub fn connect(info: &ConnectionInfo) -> ClientResult<Self> {
+        Self::connect_timeout(info, CLIENT_CONNECT_TIMEOUT)
+    }
+
+    /// Same as [`Client::connect`], `timeout` also applies to every read and write
+    pub fn connect_timeout(info: &ConnectionInfo, timeout: Duration) -> ClientResult<Self> {
+        if info.ssl_mode.requires_tls() {
+            return Err(ClientError::TlsUnsupported(info.ssl_mode));
+        }
+
+        let mut client = Self {
+            stream: Stream::connect(info, timeout)?,
+            parameters: HashMap::new(),
+            backend_pid: 0,
+            closed: false,
+        };
+
+        client
+            .stream
+            .write_all(&startup_message(&info.user, &info.database))?;
+        client.authenticate(info)?;
+        client.wait_until_ready_for_query()?;
+
+        Ok(client)
+    }
+
+    /// Value of a parameter the server reported, e.g. `server_version`,
+    /// `server_encoding` or `TimeZone`
+    #[inline]
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters.get(name).map(String::as_str)
+    }
+
+    /// `server_version` reported by the server during the startup
+    #[inline]
+    pub fn server_version(&self) -> Option<&str> {
+        self.parameter("server_version")
+    }
+
+    /// Process id of the backend serving this connection
+    #[inline]
+    pub fn backend_pid(&self) -> i32 {
+        self.backend_pid
+    }
+
+    /// Runs `sql` through the simple query protocol. Several statements
+    /// separated by `;` run in a single implicit transaction, unless they
+    /// manage transactions themselves. Returns one result per statement.
+    pub fn simple_query(&mut self, sql: &str) -> ClientResult<Vec<QueryResult>> {
+        FrontendMessage::new(b'Q')
+            .cstr(sql)
+            .send(&mut self.stream)?;
+
+        let mut results = Vec::new();
+        let mut current = QueryResult::default();
+        let mut error = None;
+
+        loop {
+            let message = BackendMessage::read(&mut self.stream)?;
+            let mut reader = message.reader();
+
+            match message.tag {
+                // RowDescription
+                b'T' => {
+                    let count = reader.i16()?;
+                    for _ in 0..count {
+                        current.columns.push(reader.cstr()?);
+                        // table oid, column number, type oid, size, modifier, format
+                        reader.bytes(18)?;
+                    }
+                }
+                // DataRow
+                b'D' => {
+                    let count = reader.i16()?;
+                    let mut row = Vec::with_capacity(count.max(0) as usize);
+                    for _ in 0..count {
+                        let value = match reader.i32()? {
+                            -1 => None,
+                            len => {
+                                let bytes = reader.bytes(len.max(0) as usize)?;
+                                Some(String::from_utf8_lossy(bytes).into_owned())
+                            }
+                        };
+                        row.push(value);
+                    }
+                    current.rows.push(row);
+                }
+                // CommandComplete
+                b'C' => {
+                    current.command_tag = reader.cstr()?;
+                    results.push(std::mem::take(&mut current));
+                }
+                // EmptyQueryResponse
+                b'I' => {}
+                b'E' => error = Some(parse_error_fields(&message.body)),
+                // CopyInResponse, there's nothing to send
+                b'G' => FrontendMessage::new(b'f')
+                    .cstr("COPY FROM STDIN is not supported")
+                    .send(&mut self.stream)?,
+                // CopyOutResponse, CopyData and CopyDone, the data is dropped
+                b'H' | b'd' | b'c' => {}
+                // ReadyForQuery, the server processed the whole query string
+                b'Z' => break,
+                _ => self.handle_async_message(&message)?,
+            }
+        }
+
+        match error {
+            Some(error) => Err(error.into()),
+            None => Ok(results),
+        }
+    }
+
+    /// Runs `sql`, discarding any rows
+    pub fn execute(&mut self, sql: &str) -> ClientResult<()> {
+        self.simple_query(sql).map(|_| ())
+    }
+
+    /// First column of the first row `sql` returns, `None` for no rows or `NULL`
+    pub fn query_scalar(&mut self, sql: &str) -> ClientResult<Option<String>> {
+        let results = self.simple_query(sql)?;
+
+        Ok(results
+            .into_iter()
+            .rev()
+            .find(|result| !result.columns.is_empty())
+            .and_then(|result| result.rows.into_iter().next())
+            .and_then(|row| row.into_iter().next())
+            .flatten())
+    }
+
+    /// Persists `name = value` in `postgresql.auto.conf` through `ALTER SYSTEM`.
+    /// Returns whether the setting only takes effect after a restart, a
+    /// reload is enough otherwise
+    pub fn alter_system(&mut self, name: &str, value: &str) -> ClientResult<bool> {
+        // custom settings are namespaced, `myapp.flag`
+        let ident = name
+            .split('.')
+            .map(quote_ident)
+            .collect::<Vec<_>>()
+            .join(".");
+
+        self.execute(&format!(
+            "ALTER SYSTEM SET {ident} = {}",
+            quote_literal(value)
+        ))?;
+
+        let context = self.query_scalar(&format!(
+            "SELECT context FROM pg_settings WHERE name = {}",
+            quote_literal(&name.to_lowercase())
+        ))?;
+
+        Ok(context.as_deref() == Some("postmaster"))
+    }
+
+    /// Tells the server the session ends, dropping the client does the same
+    pub fn close(mut self) -> ClientResult<()> {
+        self.terminate()?;
+        Ok(())
+    }
+
+    /// Sends `Terminate` once, the server closes the connection right after
+    fn terminate(&mut self) -> io::Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+
+        self.closed = true;
+        FrontendMessage::new(b'X').send(&mut self.stream)
+    }
+
+    fn authenticate(&mut self, info: &ConnectionInfo) -> ClientResult<()> {
+        let password = || {
+            info.password
+                .as_deref()
+                .ok_or(ClientError::PasswordRequired)
+        };
+        let mut scram: Option<ScramSha256> = None;
+
+        loop {
+            let message = BackendMessage::read(&mut self.stream)?;
+            let mut reader = message.reader();
+
+            match message.tag {
+                b'R' => {}
+                b'E' => return Err(parse_error_fields(&message.body).into()),
+                b'v' => {
+                    log_debug!("the server doesn't support the requested protocol minor version");
+                    continue;
+                }
+                _ => {
+                    return Err(ClientError::Protocol(format!(
+                        "unexpected message `{}` during authentication",
+                        message.tag as char
+                    )));
+                }
+            }
+
+            match reader.i32()? {
+                // AuthenticationOk
+                0 => return Ok(()),
+                // AuthenticationCleartextPassword
+                3 => FrontendMessage::new(b'p')
+                    .cstr(password()?)
+                    .send(&mut self.stream)?,
+                // AuthenticationMD5Password
+                5 => {
+                    let salt = reader.bytes(4)?;
+                    FrontendMessage::new(b'p')
+                        .cstr(&md5_password(&info.user, password()?, salt))
+                        .send(&mut self.stream)?
+                }
+                // AuthenticationSASL
+                10 => {
+                    let mut mechanisms = Vec::new();
+                    while !reader.is_empty() {
+                        match reader.cstr()? {
+                            mechanism if mechanism.is_empty() => break,
+                            mechanism => mechanisms.push(mechanism),
+                        }
+                    }
+
+                    // `SCRAM-SHA-256-PLUS` needs channel binding, which needs TLS
+                    if !mechanisms
+                        .iter()
+                        .any(|mechanism| mechanism == SCRAM_SHA_256)
+                    {
+                        return Err(ClientError::UnsupportedAuth(mechanisms.join(", ")));
+                    }
+
+                    let exchange = ScramSha256::new(password()?)?;
+                    let client_first = exchange.client_first();
+
+                    FrontendMessage::new(b'p')
+                        .cstr(SCRAM_SHA_256)
+                        .i32(client_first.len() as i32)
+                        .bytes(client_first.as_bytes())
+                        .send(&mut self.stream)?;
+
+                    scram = Some(exchange);
+                }
+                // AuthenticationSASLContinue
+                11 => {
+                    let exchange = scram.as_mut().ok_or_else(|| {
+                        ClientError::Protocol("SASL continue without SASL".into())
+                    })?;
+                    let server_first = String::from_utf8_lossy(reader.rest()).into_owned();
+                    let client_final = exchange.client_final(&server_first)?;
+
+                    FrontendMessage::new(b'p')
+                        .bytes(client_final.as_bytes())
+                        .send(&mut self.stream)?;
+                }
+                // AuthenticationSASLFinal
+                12 => {
+                    let exchange = scram
+                        .as_ref()
+                        .ok_or_else(|| ClientError::Protocol("SASL final without SASL".into()))?;
+                    exchange.verify(&String::from_utf8_lossy(reader.rest()))?;
+                }
+                // Kerberos, SCM credentials, GSSAPI, SSPI
+                method => return Err(ClientError::UnsupportedAuth(format!("code {method}"))),
+            }
+        }
+    }
+
+    /// Collects the `ParameterStatus` and `BackendKeyData` messages following
+    /// a successful authentication
+    fn wait_until_ready_for_query(&mut self) -> ClientResult<()> {
+        loop {
+            let message = BackendMessage::read(&mut self.stream)?;
+
+            match message.tag {
+                b'Z' => return Ok(()),
+                b'E' => return Err(parse_error_fields(&message.body).into()),
+                // BackendKeyData
+                b'K' => self.backend_pid = message.reader().i32()?,
+                _ => self.handle_async_message(&message)?,
+            }
+        }
+    }
+
+    /// Messages the server may send at any time
+    fn handle_async_message(&mut self, message: &BackendMessage) -> ClientResult<()> {
+        let mut reader = message.reader();
+
+        match message.tag {
+            // ParameterStatus
+            b'S' => {
+                let name = reader.cstr()?;
+                let value = reader.cstr()?;
+                self.parameters.insert(name, value);
+            }
+            // NoticeResponse
+            b'N' => {
+                let _notice = parse_error_fields(&message.body);
+                log_debug!(notice = %_notice, "server notice");
+            }
+            // NotificationResponse
+            b'A' => {}
+            tag => {
+                return Err(ClientError::Protocol(format!(
+                    "unexpected message `{}`",
+                    tag as char
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if let Err(_err) = self.terminate() {
+            log_warn!(error = %_err, "failed to terminate the session");
+        }
+    }
+}
+
+impl Stream {
+    /// A [`ConnectionInfo::host`] starting with `/` is a socket directory, like with libpq
+    fn connect(info: &ConnectionInfo, timeout: Duration) -> io::Result<Self> {
+        #[cfg(unix)]
+        if info.is_socket() {
+            let path = std::path::Path::new(&info.host).join(format!(".s.PGSQL.{}", info.port));
+            let stream = std::os::unix::net::UnixStream::connect(path)?;
+            stream.set_read_timeout(Some(timeout))?;
+            stream.set_write_timeout(Some(timeout))?;
+            return Ok(Self::Unix(stream));
+        }
+
+        let mut last_err = None;
+
+        // `localhost` may resolve to `::1` first while the server only listens on IPv4
+        for addr in (info.host.as_str(), info.port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => {
+                    stream.set_nodelay(true)?;
+                    stream.set_read_timeout(Some(timeout))?;
+                    stream.set_write_timeout(Some(timeout))?;
+                    return Ok(Self::Tcp(stream));
+                }
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("`{}` did not resolve to any address", info.host),
+            )
+        }))
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::io::{self, Read, Write};
+
+use super::error::{ClientError, ClientResult, DbError};
+
+/// Protocol 3.0 as sent in the startup message
+pub(crate) const PROTOCOL_VERSION: i32 = 196608;
+
+/// Upper bound of a single backend message, protects against reading garbage
+/// from something that isn't a PostgreSQL server
+const MAX_MESSAGE_LEN: usize = 64 * 1024 * 1024;
+
+/// A message sent by the server, the first byte of the frame is its tag
+#[derive(Debug)]
+pub(crate) struct BackendMessage {
+    pub tag: u8,
+    pub body: Vec<u8>,
+}
+
+impl BackendMessage {
+    pub fn read(stream: &mut impl Read) -> ClientResult<Self> {
+        let mut header = [0; 5];
+        stream.read_exact(&mut header)?;
+
+        let len = i32::from_be_bytes([header[1], header[2], header[3], header[4]]);
+        let len = usize::try_from(len)
+            .ok()
+            .and_then(|len| len.checked_sub(4))
+            .filter(|&len| len <= MAX_MESSAGE_LEN)
+            .ok_or_else(|| ClientError::Protocol(format!("invalid message length {len}")))?;
+
+        let mut body = vec![0; len];
+        stream.read_exact(&mut body)?;
+
+        Ok(Self {
+            tag: header[0],
+            body,
+        })
+    }
+
+    #[inline]
+    pub fn reader(&self) -> BodyReader<'_> {
+        BodyReader(&self.body)
+    }
+}
+
+/// Cursor over the body of a [`BackendMessage`]
+pub(crate) struct BodyReader<'a>(&'a [u8]);
+
+impl<'a> BodyReader<'a> {
+    pub fn u8(&mut self) -> ClientResult<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn i16(&mut self) -> ClientResult<i16> {
+        let bytes = self.bytes(2)?;
+        Ok(i16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn i32(&mut self) -> ClientResult<i32> {
+        let bytes = self.bytes(4)?;
+        Ok(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn bytes(&mut self, len: usize) -> ClientResult<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(ClientError::Protocol("truncated message".into()));
+        }
+
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    /// A null terminated string
+    pub fn cstr(&mut self) -> ClientResult<String> {
+        let end = self
+            .0
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| ClientError::Protocol("unterminated string".into()))?;
+
+        let value = String::from_utf8_lossy(&self.0[..end]).into_owned();
+        self.0 = &self.0[end + 1..];
+        Ok(value)
+    }
+
+    /// Everything not read yet
+    pub fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.0)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Builds a frontend message, the length is filled in by [`FrontendMessage::send`]
+pub(crate) struct FrontendMessage {
+    buf: Vec<u8>,
+    /// Offset of the length field, `1` for tagged messages
+    len_at: usize,
+}
+
+impl FrontendMessage {
+    pub fn new(tag: u8) -> Self {
+        Self {
+            buf: vec![tag, 0, 0, 0, 0],
+            len_at: 1,
+        }
+    }
+
+    /// The startup message is the only one without a tag
+    pub fn untagged() -> Self {
+        Self {
+            buf: vec![0, 0, 0, 0],
+            len_at: 0,
+        }
+    }
+
+    pub fn i32(mut self, value: i32) -> Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn bytes(mut self, value: &[u8]) -> Self {
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    pub fn cstr(mut self, value: &str) -> Self {
+        self.buf.extend_from_slice(value.as_bytes());
+        self.buf.push(0);
+        self
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let len = (self.buf.len() - self.len_at) as i32;
+        self.buf[self.len_at..self.len_at + 4].copy_from_slice(&len.to_be_bytes());
+        self.buf
+    }
+
+    pub fn send(self, stream: &mut impl Write) -> io::Result<()> {
+        stream.write_all(&self.finish())
+    }
+}
+
+/// `StartupMessage` for `user` and `database`
+pub(crate) fn startup_message(user: &str, database: &str) -> Vec<u8> {
+    FrontendMessage::untagged()
+        .i32(PROTOCOL_VERSION)
+        .cstr("user")
+        .cstr(user)
+        .cstr("database")
+        .cstr(database)
+        .bytes(&[0])
+        .finish()
+}
+
+/// Fields of an `ErrorResponse` or `NoticeResponse` body
+pub(crate) fn parse_error_fields(body: &[u8]) -> DbError {
+    let mut error = DbError::default();
+    let mut reader = BodyReader(body);
+
+    while let Ok(field) = reader.u8() {
+        if field == 0 {
+            break;
+        }
+
+        let Ok(value) = reader.cstr() else {
+            break;
+        };
+
+        match field {
+            // `V` is never localized, unlike `S`
+            b'V' => error.severity = value,
+            b'S' if error.severity.is_empty() => error.severity = value,
+            b'C' => error.code = value,
+            b'M' => error.message = value,
+            b'D' => error.detail = Some(value),
+            b'H' => error.hint = Some(value),
+            b'P' => error.position = value.parse().ok(),
+            _ => {}
+        }
+    }
+
+    error
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(tag: u8, len: i32, body: &[u8]) -> Vec<u8> {
+        let mut frame = vec![tag];
+        frame.extend_from_slice(&len.to_be_bytes());
+        frame.extend_from_slice(body);
+        frame
+    }
+
+    #[test]
+    fn read_takes_the_length_from_the_header() {
+        let mut stream = frame(b'Z', 5, b"I")
+            .into_iter()
+            .chain(*b"rest")
+            .collect::<Vec<_>>();
+        let message = BackendMessage::read(&mut stream.as_slice()).unwrap();
+        assert_eq!(
+            (message.tag, message.body.as_slice()),
+            (b'Z', b"I".as_slice())
+        );
+
+        // a length of 4 is a message without a body
+        stream = frame(b'I', 4, b"");
+        let message = BackendMessage::read(&mut stream.as_slice()).unwrap();
+        assert!(message.body.is_empty());
+    }
+
+    #[test]
+    fn read_rejects_invalid_lengths() {
+        let too_long = i32::try_from(MAX_MESSAGE_LEN + 5).unwrap();
+
+        for len in [-1, 0, 3, i32::MIN, too_long] {
+            let stream = frame(b'Z', len, b"I");
+            assert!(
+                matches!(
+                    BackendMessage::read(&mut stream.as_slice()),
+                    Err(ClientError::Protocol(_))
+                ),
+                "{len}"
+            );
+        }
+    }
+
+    #[test]
+    fn read_fails_on_a_truncated_body() {
+        let stream = frame(b'Z', 8, b"I");
+        assert!(matches!(
+            BackendMessage::read(&mut stream.as_slice()),
+            Err(ClientError::IOError(err)) if err.kind() == io::ErrorKind::UnexpectedEof
+        ));
+    }
+
+    #[test]
+    fn parse_error_fields_reads_every_field() {
+        let body = b"SFEHLER\0VERROR\0C42P01\0Mrelation \"t\" does not exist\0\
+                     Dsome detail\0Hsome hint\0P15\0Ffile.c\0\0";
+        let error = parse_error_fields(body);
+
+        assert_eq!(
+            error,
+            DbError {
+                severity: "ERROR".into(),
+                code: "42P01".into(),
+                message: "relation \"t\" does not exist".into(),
+                detail: Some("some detail".into()),
+                hint: Some("some hint".into()),
+                position: Some(15),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_error_fields_tolerates_garbage() {
+        // the localized severity is the fallback without `V`
+        let error = parse_error_fields(b"SFATAL\0C28P01\0Mno\0Pnot a number\0");
+        assert_eq!(error.severity, "FATAL");
+        assert_eq!(error.position, None);
+
+        // an unterminated field ends the parsing, earlier ones are kept
+        let error = parse_error_fields(b"C42601\0Msyntax err");
+        assert_eq!(error.code, "42601");
+        assert!(error.message.is_empty());
+
+        assert_eq!(parse_error_fields(b""), DbError::default());
+    }
+}
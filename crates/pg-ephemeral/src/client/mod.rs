@@ -0,0 +1,9 @@
+mod auth;
+mod error;
+mod impls;
+pub(crate) mod message;
+mod reload;
+
+pub use error::{ClientError, ClientResult, DbError};
+pub use impls::{Client, QueryResult};
+pub(crate) use reload::reload;
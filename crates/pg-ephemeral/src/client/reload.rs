@@ -0,0 +1,34 @@
+use std::time::{Duration, Instant};
+
+use super::{Client, ClientError};
+
+/// Runs `trigger` and blocks until the server re-read its configuration
+/// files, which it does asynchronously. The session behind `client` picks up
+/// the reload only after the postmaster did, so new sessions see the new
+/// configuration once `pg_conf_load_time()` of this one changed.
+/// Returns `false` when that didn't happen within `timeout`.
+pub(crate) fn reload<E>(
+    client: &mut Client,
+    timeout: Duration,
+    poll_interval: Duration,
+    trigger: impl FnOnce(&mut Client) -> Result<(), E>,
+) -> Result<bool, E>
+where
+    E: From<ClientError>,
+{
+    let load_time = |client: &mut Client| client.query_scalar("SELECT pg_conf_load_time()");
+
+    let before = load_time(client)?;
+    trigger(client)?;
+
+    let deadline = Instant::now() + timeout;
+    while load_time(client)? == before {
+        if Instant::now() >= deadline {
+            return Ok(false);
+        }
+
+        std::thread::sleep(poll_interval);
+    }
+
+    Ok(true)
+}
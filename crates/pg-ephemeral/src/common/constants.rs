@@ -1,22 +1,68 @@
 use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
 
-pub const DEFAULT_DB_USER: &'static str = "pg-user";
-pub const DEFAULT_DB_PASSWORD: &'static str = "pg-secret";
-pub const DEFAULT_DB_NAME: &'static str = "pg-temp";
+pub const DEFAULT_DB_USER: &str = "pg-user";
+pub const DEFAULT_DB_PASSWORD: &str = "pg-secret";
+pub const DEFAULT_DB_NAME: &str = "pg-temp";
 pub const DEFAULT_DB_HOST: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
 pub const DEFAULT_DB_PORT: u16 = 5433;
 
+/// PostgreSQL truncates identifiers longer than this (`NAMEDATALEN - 1`)
+pub const PG_MAX_IDENT_LEN: usize = 63;
+
+/// Defaults of [`crate::Readiness`]
+pub const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Upper bound of a single readiness probe, unless the poll interval is longer
+pub const READINESS_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Default of [`crate::Shutdown::timeout`], the server is killed afterwards
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default connect, read and write timeout of [`crate::client::Client`]
+pub const CLIENT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Directory inside the system temp dir holding the per-port lock files
+pub const PORT_LOCK_DIR: &str = "pg-ephemeral-ports";
+
 // [Containerized] related
 #[cfg(feature = "containerized")]
 mod containerized {
+    use std::time::Duration;
+
     use crate::containerized::PgImageTag;
 
-    pub const CONTAINERIZED_IMAGE_NAME: &'static str = "postgres";
+    pub const CONTAINERIZED_IMAGE_NAME: &str = "postgres";
     pub const CONTAINERIZED_IMAGE_TAG: PgImageTag = PgImageTag::V175;
-    pub const CONTAINERIZED_CONTAINER_NAME: &'static str = "pg-ephemeral";
-    pub const CONTAINERIZED_ENV_PASSWORD: &'static str = "POSTGRES_PASSWORD";
-    pub const CONTAINERIZED_ENV_USER: &'static str = "POSTGRES_USER";
-    pub const CONTAINERIZED_ENV_DB: &'static str = "POSTGRES_DB";
+    /// Prefix of the generated container names, `pg-ephemeral-<session>-<n>`
+    pub const CONTAINERIZED_CONTAINER_NAME: &str = "pg-ephemeral";
+    /// Labels identifying containers created by this crate
+    pub const CONTAINERIZED_LABEL_PREFIX: &str = "dev.pg-ephemeral.";
+    pub const CONTAINERIZED_LABEL_MANAGED: &str = "dev.pg-ephemeral.managed";
+    pub const CONTAINERIZED_LABEL_PID: &str = "dev.pg-ephemeral.pid";
+    pub const CONTAINERIZED_LABEL_SESSION: &str = "dev.pg-ephemeral.session";
+    pub const CONTAINERIZED_LABEL_HOST: &str = "dev.pg-ephemeral.host";
+    pub const CONTAINERIZED_LABEL_BOOT: &str = "dev.pg-ephemeral.boot";
+    pub const CONTAINERIZED_LABEL_CREATED: &str = "dev.pg-ephemeral.created";
+    pub const CONTAINERIZED_LABEL_FINGERPRINT: &str = "dev.pg-ephemeral.fingerprint";
+    /// Template the database of a reused container is recreated from on every run
+    pub const CONTAINERIZED_REUSE_TEMPLATE: &str = "pg_ephemeral_reuse_template";
+    /// Directory the image entrypoint runs init scripts from
+    pub const CONTAINERIZED_INIT_DIR: &str = "/docker-entrypoint-initdb.d";
+    /// File names the image entrypoint picks up as init scripts
+    pub const CONTAINERIZED_INIT_EXTENSIONS: [&str; 5] =
+        [".sh", ".sql", ".sql.gz", ".sql.xz", ".sql.zst"];
+    /// Pulling the image isn't included, but the init scripts are
+    pub const CONTAINERIZED_STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+    pub const CONTAINERIZED_POLL_INTERVAL: Duration = Duration::from_millis(250);
+    pub const CONTAINERIZED_ENV_PASSWORD: &str = "POSTGRES_PASSWORD";
+    pub const CONTAINERIZED_ENV_USER: &str = "POSTGRES_USER";
+    pub const CONTAINERIZED_ENV_DB: &str = "POSTGRES_DB";
+    pub const CONTAINERIZED_ENV_PORT: &str = "PGPORT";
+    /// Command the image entrypoint execs into, the default `CMD` of the official image
+    pub const CONTAINERIZED_PROGRAM_POSTGRES: &str = "postgres";
+    /// Server settings derived from the container config, never user supplied
+    pub const CONTAINERIZED_MANAGED_SERVER_CONFIGS: [&str; 2] = ["port", "listen_addresses"];
 }
 
 #[cfg(feature = "containerized")]
@@ -25,9 +71,65 @@ pub use containerized::*;
 // [Local] related
 #[cfg(feature = "local")]
 mod local {
-    pub const LOCAL_PROGRAM_POSTGRES: &'static str = "postgres";
-    pub const LOCAL_PROGRAM_INITDB: &'static str = "initdb";
-    pub const LOCAL_TMP_DIR_PREFIX: &'static str = "pgtemp-";
+    use std::time::Duration;
+
+    pub const LOCAL_PROGRAM_POSTGRES: &str = "postgres";
+    pub const LOCAL_PROGRAM_INITDB: &str = "initdb";
+    pub const LOCAL_PROGRAM_PSQL: &str = "psql";
+    pub const LOCAL_PROGRAM_PG_DUMP: &str = "pg_dump";
+    pub const LOCAL_PROGRAM_PG_RESTORE: &str = "pg_restore";
+    pub const LOCAL_TMP_DIR_PREFIX: &str = "pgtemp-";
+    pub const LOCAL_DATA_DIR: &str = "data";
+    pub const LOCAL_LOG_FILE: &str = "postgres.log";
+    pub const LOCAL_PASSWORD_FILE: &str = "pwfile";
+    /// Marker inside the temp dir naming the owning process, see [`crate::reaper`]
+    pub const LOCAL_OWNER_FILE: &str = "owner";
+    pub const LOCAL_MAINTENANCE_DB: &str = "postgres";
+    pub const LOCAL_INITDB_CACHE_DIR: &str = "pg-ephemeral-initdb";
+    /// Server settings derived from the instance config, never user supplied
+    pub const LOCAL_MANAGED_SERVER_CONFIGS: [&str; 4] = [
+        "port",
+        "listen_addresses",
+        "unix_socket_directories",
+        "data_directory",
+    ];
+    /// `initdb` arguments derived from the instance config, never user supplied.
+    /// Long names only, short options are normalized with [`LOCAL_INITDB_SHORT_ARGS`]
+    pub const LOCAL_MANAGED_INITDB_ARGS: [&str; 8] = [
+        "pgdata",
+        "username",
+        "pwfile",
+        "pwprompt",
+        "auth",
+        "auth-local",
+        "auth-host",
+        "no-sync",
+    ];
+    /// Short `initdb` options and the long ones they stand for
+    pub const LOCAL_INITDB_SHORT_ARGS: [(&str, &str); 16] = [
+        ("A", "auth"),
+        ("c", "set"),
+        ("D", "pgdata"),
+        ("d", "debug"),
+        ("E", "encoding"),
+        ("g", "allow-group-access"),
+        ("k", "data-checksums"),
+        ("N", "no-sync"),
+        ("n", "no-clean"),
+        ("S", "sync-only"),
+        ("s", "show"),
+        ("T", "text-search-config"),
+        ("U", "username"),
+        ("V", "version"),
+        ("W", "pwprompt"),
+        ("X", "waldir"),
+    ];
+    /// User `initdb` and `postgres` run as when started by root, see
+    /// `LocalBuilder::with_create_unprivileged_user`
+    pub const LOCAL_UNPRIVILEGED_USER: &str = "postgres";
+    pub const LOCAL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+    /// How often startup is retried with a new port when another process bound ours first
+    pub const LOCAL_PORT_RETRIES: usize = 5;
 }
 
 #[cfg(feature = "local")]
@@ -0,0 +1,78 @@
+use sha2::{Digest, Sha256};
+
+/// SHA-256 over a sequence of fields.
+///
+/// Unlike [`std::hash::DefaultHasher`], the result never changes between Rust
+/// releases, so it can name things outliving the process: cache entries on
+/// disk or containers kept across runs.
+#[derive(Debug, Clone, Default)]
+pub struct Fingerprint(Sha256);
+
+impl Fingerprint {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `field`, length prefixed so `("ab", "c")` and `("a", "bc")` differ
+    pub fn update(&mut self, field: impl AsRef<[u8]>) {
+        let field = field.as_ref();
+        self.0.update((field.len() as u64).to_le_bytes());
+        self.0.update(field);
+    }
+
+    /// Adds `field`, a missing one differs from an empty one
+    pub fn update_opt(&mut self, field: Option<impl AsRef<[u8]>>) {
+        match field {
+            Some(field) => {
+                self.0.update([1]);
+                self.update(field);
+            }
+            None => self.0.update([0]),
+        }
+    }
+
+    /// The first `len` hex digits of the digest
+    pub fn hex(self, len: usize) -> String {
+        let mut hex: String = self
+            .0
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+
+        hex.truncate(len);
+        hex
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprint(fields: &[&str]) -> String {
+        let mut fingerprint = Fingerprint::new();
+        fields.iter().for_each(|field| fingerprint.update(field));
+        fingerprint.hex(64)
+    }
+
+    #[test]
+    fn stable_output() {
+        // a changed value orphans every cache entry and reused container
+        assert_eq!(
+            fingerprint(&["pg-ephemeral"]),
+            "0e61e1fbe8e6713ad5e419d58b4efb7c183a99ffe02bb542ce433746a740d90e"
+        );
+    }
+
+    #[test]
+    fn fields_are_delimited() {
+        assert_ne!(fingerprint(&["ab", "c"]), fingerprint(&["a", "bc"]));
+        assert_ne!(fingerprint(&[""]), fingerprint(&[]));
+
+        let mut none = Fingerprint::new();
+        none.update_opt(None::<&str>);
+        let mut empty = Fingerprint::new();
+        empty.update_opt(Some(""));
+        assert_ne!(none.hex(64), empty.hex(64));
+    }
+}
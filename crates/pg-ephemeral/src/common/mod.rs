@@ -1,5 +1,13 @@
 pub mod constants;
+pub mod fingerprint;
+#[cfg(feature = "local")]
 mod password;
 pub mod port;
+pub mod readiness;
+pub mod session;
+pub mod shutdown;
+pub mod sql;
+pub mod uri;
 
+#[cfg(feature = "local")]
 pub use password::PasswordMethod;
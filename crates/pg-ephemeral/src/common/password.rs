@@ -18,27 +18,42 @@ impl PasswordMethod {
     pub fn check_valid(&self) -> EphemeralResult<()> {
         use PasswordMethod::*;
 
-        match self {
-            File { file_path } => {
-                if !std::fs::exists(file_path)
-                    .map_err(|err| EphemeralError::PasswordMethodFailed(err.to_string()))?
-                {
-                    return Err(EphemeralError::PasswordMethodFailed(
-                        "password file not found".into(),
-                    ));
-                }
-
-                if !file_path.is_file() {
-                    return Err(EphemeralError::PasswordMethodFailed(
-                        "given path is not a file".into(),
-                    ));
-                }
+        if let File { file_path } = self {
+            if !std::fs::exists(file_path)
+                .map_err(|err| EphemeralError::PasswordMethodFailed(err.to_string()))?
+            {
+                return Err(EphemeralError::PasswordMethodFailed(
+                    "password file not found".into(),
+                ));
+            }
+
+            if !file_path.is_file() {
+                return Err(EphemeralError::PasswordMethodFailed(
+                    "given path is not a file".into(),
+                ));
             }
-            _ => {}
         }
 
         Ok(())
     }
+
+    /// Reads the plain text password this method refers to
+    pub fn resolve(&self) -> std::io::Result<String> {
+        use PasswordMethod::*;
+
+        match self {
+            Text(pass) => Ok(pass.clone()),
+            File { file_path } => {
+                let content = std::fs::read_to_string(file_path)?;
+                Ok(content.trim_end_matches(['\r', '\n']).to_string())
+            }
+            #[cfg(feature = "cli")]
+            Prompt => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "prompted passwords must be resolved by the caller",
+            )),
+        }
+    }
 }
 
 impl Default for PasswordMethod {
@@ -1,30 +1,158 @@
 use std::{
+    fs::{File, TryLockError},
+    io,
     net::{IpAddr, SocketAddr, TcpListener},
+    path::PathBuf,
     sync::atomic::{AtomicU16, Ordering},
 };
 
-use super::constants::{DEFAULT_DB_HOST, DEFAULT_DB_PORT};
+use super::constants::{DEFAULT_DB_HOST, DEFAULT_DB_PORT, PORT_LOCK_DIR};
 
 static DB_PORT_COUNTER: AtomicU16 = AtomicU16::new(DEFAULT_DB_PORT);
 
-pub fn random_free_port() -> u16 {
+/// A free TCP port, reserved across processes until this value is dropped
+///
+/// Binding and dropping a listener only tells that the port was free a moment
+/// ago, parallel test processes happily pick the same one. Every reservation
+/// additionally holds an exclusive lock on `<tmp>/pg-ephemeral-ports/<port>.lock`,
+/// so other processes using this crate skip it until the server bound it.
+/// The OS releases the lock if the owning process dies.
+#[derive(Debug)]
+pub struct PortReservation {
+    port: u16,
+    _lock: File,
+}
+
+impl PortReservation {
+    #[inline]
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+/// Reserves the next port that is neither locked by another process nor bound
+pub fn reserve_free_port() -> io::Result<PortReservation> {
     let host = DEFAULT_DB_HOST;
+    let lock_dir = lock_dir()?;
+
+    // every port gets a single chance, then give up
+    for _ in DEFAULT_DB_PORT..u16::MAX {
+        let port = next_port();
 
+        let Some(lock) = try_lock_port(&lock_dir, port)? else {
+            continue;
+        };
+
+        if is_free_tcp(port, &host) {
+            return Ok(PortReservation { port, _lock: lock });
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::AddrInUse,
+        "no free port left to reserve",
+    ))
+}
+
+/// Reserves the given `port`, `None` when another process locked or bound it
+pub fn reserve_port(port: u16) -> io::Result<Option<PortReservation>> {
+    let Some(lock) = try_lock_port(&lock_dir()?, port)? else {
+        return Ok(None);
+    };
+
+    Ok(is_free_tcp(port, &DEFAULT_DB_HOST).then_some(PortReservation { port, _lock: lock }))
+}
+
+fn next_port() -> u16 {
     loop {
         let port = DB_PORT_COUNTER.fetch_add(1, Ordering::SeqCst);
 
-        if port >= u16::MAX {
+        if port == u16::MAX {
             DB_PORT_COUNTER.store(DEFAULT_DB_PORT, Ordering::SeqCst);
             continue;
         }
 
-        if is_free_tcp(port, &host) {
-            return port;
+        return port;
+    }
+}
+
+fn lock_dir() -> io::Result<PathBuf> {
+    let dir = std::env::temp_dir().join(PORT_LOCK_DIR);
+
+    if !dir.is_dir() {
+        std::fs::create_dir_all(&dir)?;
+
+        // shared by every user on the machine, like `/tmp` itself
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o1777));
         }
     }
+
+    Ok(dir)
+}
+
+/// `None` when another process holds the lock
+fn try_lock_port(lock_dir: &std::path::Path, port: u16) -> io::Result<Option<File>> {
+    let path = lock_dir.join(format!("{port}.lock"));
+
+    let file = match File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+    {
+        Ok(file) => file,
+        // lock file created by another user, treat the port as taken
+        Err(err) if err.kind() == io::ErrorKind::PermissionDenied => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    match file.try_lock() {
+        Ok(()) => Ok(Some(file)),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(err)) => Err(err),
+    }
 }
 
 fn is_free_tcp(port: u16, host: &IpAddr) -> bool {
     let socket_addr: SocketAddr = SocketAddr::new(*host, port);
     TcpListener::bind(socket_addr).is_ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_locked_port_is_skipped_until_released() {
+        let lock_dir = lock_dir().unwrap();
+        let reservation = reserve_free_port().unwrap();
+        let port = reservation.port();
+
+        assert!(try_lock_port(&lock_dir, port).unwrap().is_none());
+
+        drop(reservation);
+        assert!(try_lock_port(&lock_dir, port).unwrap().is_some());
+    }
+
+    #[test]
+    fn reservations_never_hand_out_the_same_port() {
+        let first = reserve_free_port().unwrap();
+        let second = reserve_free_port().unwrap();
+
+        assert_ne!(first.port(), second.port());
+    }
+
+    #[test]
+    fn reserve_port_refuses_reserved_and_bound_ports() {
+        let reservation = reserve_free_port().unwrap();
+        assert!(reserve_port(reservation.port()).unwrap().is_none());
+
+        let port = reservation.port();
+        drop(reservation);
+        let _listener = TcpListener::bind(SocketAddr::new(DEFAULT_DB_HOST, port)).unwrap();
+        assert!(reserve_port(port).unwrap().is_none());
+    }
+}
@@ -0,0 +1,277 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use super::constants::{DEFAULT_POLL_INTERVAL, DEFAULT_STARTUP_TIMEOUT, READINESS_PROBE_TIMEOUT};
+use crate::client::message::{parse_error_fields, startup_message};
+
+/// `cannot_connect_now`, the server is starting up, shutting down or in recovery
+const SQLSTATE_CANNOT_CONNECT_NOW: &str = "57P03";
+
+/// Longest error response body read from the server, the rest is ignored
+const MAX_ERROR_RESPONSE_LEN: usize = 8192;
+
+/// How long to wait for a server to accept connections and how often to check.
+///
+/// Readiness is checked at the protocol level, like `pg_isready`: a startup
+/// message is sent and the first answer of the server decides. Any answer
+/// other than "the database system is starting up" means the server accepts
+/// connections, authentication is never attempted. Unlike matching a log line,
+/// this can't be fooled by a temporary server, e.g. the one the official
+/// docker image runs its init scripts against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Readiness {
+    pub timeout: Duration,
+    pub poll_interval: Duration,
+}
+
+/// Outcome of a single probe, mirrors `PGPing` of libpq
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PingStatus {
+    /// The server accepts connections
+    Accepting,
+    /// The server answered, but doesn't accept connections yet
+    Rejecting,
+    /// Nothing listening, or not a PostgreSQL server
+    NoResponse,
+}
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self::new(DEFAULT_STARTUP_TIMEOUT, DEFAULT_POLL_INTERVAL)
+    }
+}
+
+impl Readiness {
+    pub const fn new(timeout: Duration, poll_interval: Duration) -> Self {
+        Self {
+            timeout,
+            poll_interval,
+        }
+    }
+
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    #[inline]
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Probes the server listening on `addr` once
+    pub fn probe_tcp(&self, addr: SocketAddr, user: &str, database: &str) -> PingStatus {
+        let timeout = self.probe_timeout();
+
+        let probe = || {
+            let stream = TcpStream::connect_timeout(&addr, timeout)?;
+            stream.set_read_timeout(Some(timeout))?;
+            stream.set_write_timeout(Some(timeout))?;
+            probe_stream(stream, user, database)
+        };
+
+        probe().unwrap_or(PingStatus::NoResponse)
+    }
+
+    /// Probes the server listening on the unix domain socket at `path` once
+    #[cfg(unix)]
+    pub fn probe_socket(&self, path: &std::path::Path, user: &str, database: &str) -> PingStatus {
+        let timeout = self.probe_timeout();
+
+        let probe = || {
+            let stream = std::os::unix::net::UnixStream::connect(path)?;
+            stream.set_read_timeout(Some(timeout))?;
+            stream.set_write_timeout(Some(timeout))?;
+            probe_stream(stream, user, database)
+        };
+
+        probe().unwrap_or(PingStatus::NoResponse)
+    }
+
+    /// Same as [`Readiness::probe_tcp`], without blocking the runtime
+    #[cfg(feature = "containerized")]
+    pub async fn probe_tcp_async(
+        &self,
+        host: &str,
+        port: u16,
+        user: &str,
+        database: &str,
+    ) -> PingStatus {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let probe = async {
+            let mut stream = tokio::net::TcpStream::connect((host, port)).await?;
+            stream.write_all(&startup_message(user, database)).await?;
+
+            let mut header = [0; 5];
+            stream.read_exact(&mut header).await?;
+
+            let mut body = vec![0; body_len(&header)];
+            stream.read_exact(&mut body).await?;
+
+            io::Result::Ok(ping_status(header[0], &body))
+        };
+
+        match tokio::time::timeout(self.probe_timeout(), probe).await {
+            Ok(Ok(status)) => status,
+            _ => PingStatus::NoResponse,
+        }
+    }
+
+    /// A single probe never outlasts the poll interval by much, a hanging
+    /// connection would otherwise eat into the whole timeout
+    fn probe_timeout(&self) -> Duration {
+        self.poll_interval
+            .max(READINESS_PROBE_TIMEOUT)
+            .min(self.timeout.max(Duration::from_millis(1)))
+    }
+}
+
+/// Sends the startup message and reads the first answer
+fn probe_stream(
+    mut stream: impl Read + Write,
+    user: &str,
+    database: &str,
+) -> io::Result<PingStatus> {
+    stream.write_all(&startup_message(user, database))?;
+
+    let mut header = [0; 5];
+    stream.read_exact(&mut header)?;
+
+    let mut body = vec![0; body_len(&header)];
+    stream.read_exact(&mut body)?;
+
+    Ok(ping_status(header[0], &body))
+}
+
+/// Length of the message body following the type byte and length of `header`
+fn body_len(header: &[u8; 5]) -> usize {
+    let len = i32::from_be_bytes([header[1], header[2], header[3], header[4]]);
+    (len.max(4) as usize - 4).min(MAX_ERROR_RESPONSE_LEN)
+}
+
+/// Decides on the first message of the server, the same way `PQping` does
+fn ping_status(tag: u8, body: &[u8]) -> PingStatus {
+    match tag {
+        // authentication request or protocol negotiation, the server is up
+        b'R' | b'v' => PingStatus::Accepting,
+        // a wrong password, unknown database or missing `pg_hba.conf` entry
+        // still means the server accepts connections
+        b'E' => match parse_error_fields(body).code.as_str() {
+            SQLSTATE_CANNOT_CONNECT_NOW => PingStatus::Rejecting,
+            _ => PingStatus::Accepting,
+        },
+        _ => PingStatus::NoResponse,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replays `reply` to whatever is written to it
+    struct Server {
+        reply: io::Cursor<Vec<u8>>,
+    }
+
+    impl Server {
+        fn replying(tag: u8, body: &[u8]) -> Self {
+            let mut reply = vec![tag];
+            reply.extend_from_slice(&(body.len() as i32 + 4).to_be_bytes());
+            reply.extend_from_slice(body);
+            Self::raw(reply)
+        }
+
+        fn raw(reply: Vec<u8>) -> Self {
+            Self {
+                reply: io::Cursor::new(reply),
+            }
+        }
+    }
+
+    impl Read for Server {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reply.read(buf)
+        }
+    }
+
+    impl Write for Server {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn probe(server: Server) -> io::Result<PingStatus> {
+        probe_stream(server, "postgres", "postgres")
+    }
+
+    #[test]
+    fn an_authentication_request_is_accepting() {
+        // AuthenticationSASL
+        let server = Server::replying(b'R', b"\0\0\0\x0aSCRAM-SHA-256\0\0");
+        assert_eq!(probe(server).unwrap(), PingStatus::Accepting);
+
+        // negotiation of an unsupported protocol minor version
+        let server = Server::replying(b'v', b"\0\0\0\0\0\0\0\0");
+        assert_eq!(probe(server).unwrap(), PingStatus::Accepting);
+    }
+
+    #[test]
+    fn cannot_connect_now_is_rejecting() {
+        let server = Server::replying(
+            b'E',
+            b"SFATAL\0VFATAL\0C57P03\0Mthe database system is starting up\0\0",
+        );
+        assert_eq!(probe(server).unwrap(), PingStatus::Rejecting);
+    }
+
+    #[test]
+    fn other_errors_are_accepting() {
+        let server = Server::replying(b'E', b"SFATAL\0C28P01\0Mpassword authentication failed\0\0");
+        assert_eq!(probe(server).unwrap(), PingStatus::Accepting);
+    }
+
+    #[test]
+    fn no_or_foreign_replies_are_no_response() {
+        assert!(probe(Server::raw(Vec::new())).is_err());
+
+        // e.g. an HTTP server, its reply reads as a message far longer than it sends
+        let server = Server::raw(b"HTTP/1.1 400 Bad Request\r\n\r\n".to_vec());
+        assert!(probe(server).is_err());
+
+        // complete messages of an unexpected type
+        let server = Server::replying(b'Z', b"I");
+        assert_eq!(probe(server).unwrap(), PingStatus::NoResponse);
+    }
+
+    #[test]
+    fn truncated_replies_fail() {
+        // the header announces more than the server sends
+        let mut reply = vec![b'E'];
+        reply.extend_from_slice(&64i32.to_be_bytes());
+        reply.extend_from_slice(b"SFATAL\0C57P03\0");
+        assert!(probe(Server::raw(reply)).is_err());
+
+        // cut off inside the header
+        assert!(probe(Server::raw(vec![b'R', 0, 0])).is_err());
+    }
+
+    #[test]
+    fn body_len_is_clamped() {
+        assert_eq!(body_len(&[b'R', 0, 0, 0, 8]), 4);
+        // lengths below the length field itself, including negative ones
+        assert_eq!(body_len(&[b'R', 0, 0, 0, 2]), 0);
+        assert_eq!(body_len(&[b'R', 0xff, 0xff, 0xff, 0xff]), 0);
+        assert_eq!(
+            body_len(&[b'E', 0x7f, 0xff, 0xff, 0xff]),
+            MAX_ERROR_RESPONSE_LEN
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::platform::sys::{Sys, SysInfo, SysT};
+
+static SESSION_ID: OnceLock<String> = OnceLock::new();
+static HOST_ID: OnceLock<String> = OnceLock::new();
+static BOOT_ID: OnceLock<Option<String>> = OnceLock::new();
+
+/// Random id of the current process, shared by every instance it creates.
+///
+/// Unlike the pid it isn't recycled, so resources left behind by a crashed
+/// process can't be mistaken for ones of a live process with the same pid.
+pub fn session_id() -> &'static str {
+    SESSION_ID.get_or_init(|| {
+        // `RandomState` is seeded from the OS random source
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(std::process::id());
+        hasher.write_u64(unix_timestamp());
+
+        format!("{:016x}", hasher.finish())
+    })
+}
+
+/// `<hostname>/<machine id>` of this machine, the machine id is left out
+/// where the platform doesn't have one.
+///
+/// Pids only mean something on the machine that handed them out, a docker
+/// daemon may be shared by several of them.
+pub fn host_id() -> &'static str {
+    HOST_ID.get_or_init(|| {
+        let hostname = Sys::new().map(|sys| sys.sysname()).unwrap_or_default();
+
+        match read_id(&["/etc/machine-id", "/var/lib/dbus/machine-id"]) {
+            Some(machine_id) => format!("{hostname}/{machine_id}"),
+            None => hostname,
+        }
+    })
+}
+
+/// Random id the kernel picks on every boot, `None` outside of linux.
+/// Resources of an earlier boot are stale no matter what their pid says
+pub fn boot_id() -> Option<&'static str> {
+    BOOT_ID
+        .get_or_init(|| read_id(&["/proc/sys/kernel/random/boot_id"]))
+        .as_deref()
+}
+
+/// Trimmed content of the first non-empty file of `paths`
+fn read_id(paths: &[&str]) -> Option<String> {
+    paths.iter().find_map(|path| {
+        let id = std::fs::read_to_string(path).ok()?;
+        let id = id.trim();
+        (!id.is_empty()).then(|| id.to_string())
+    })
+}
+
+/// Seconds since the unix epoch
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
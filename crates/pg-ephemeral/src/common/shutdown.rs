@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use super::constants::DEFAULT_SHUTDOWN_TIMEOUT;
+use crate::platform::sys::Signal;
+
+/// The shutdown modes of the server, see the "Shutting Down the Server"
+/// chapter of the PostgreSQL docs
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownMode {
+    /// Waits for all clients to disconnect, a leftover connection pool keeps
+    /// the server alive until the grace timeout kills it
+    Smart,
+    /// Disconnects clients, rolls back their transactions and writes a checkpoint
+    #[default]
+    Fast,
+    /// Exits without a checkpoint, like a crash: the next start runs WAL recovery
+    Immediate,
+}
+
+impl ShutdownMode {
+    /// Value of `pg_ctl stop --mode`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Smart => "smart",
+            Self::Fast => "fast",
+            Self::Immediate => "immediate",
+        }
+    }
+
+    /// Signal the postmaster maps to this mode
+    pub(crate) fn signal(&self) -> Signal {
+        match self {
+            Self::Smart => Signal::Term,
+            Self::Fast => Signal::Int,
+            Self::Immediate => Signal::Quit,
+        }
+    }
+
+    /// Name of [`ShutdownMode::signal`] as understood by `kill -s`
+    pub(crate) fn signal_name(&self) -> &'static str {
+        match self {
+            Self::Smart => "TERM",
+            Self::Fast => "INT",
+            Self::Immediate => "QUIT",
+        }
+    }
+}
+
+impl std::fmt::Display for ShutdownMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How to stop the server: the [`ShutdownMode`] requested first and how long
+/// the server gets to follow it before it is killed with `SIGKILL`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shutdown {
+    pub mode: ShutdownMode,
+    pub timeout: Duration,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new(ShutdownMode::default(), DEFAULT_SHUTDOWN_TIMEOUT)
+    }
+}
+
+impl Shutdown {
+    pub const fn new(mode: ShutdownMode, timeout: Duration) -> Self {
+        Self { mode, timeout }
+    }
+
+    #[inline]
+    pub fn with_mode(mut self, mode: ShutdownMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl From<ShutdownMode> for Shutdown {
+    fn from(mode: ShutdownMode) -> Self {
+        Self::default().with_mode(mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes_map_to_the_postmaster_signals() {
+        let cases = [
+            (ShutdownMode::Smart, Signal::Term, "TERM"),
+            (ShutdownMode::Fast, Signal::Int, "INT"),
+            (ShutdownMode::Immediate, Signal::Quit, "QUIT"),
+        ];
+
+        for (mode, signal, name) in cases {
+            assert_eq!(mode.signal(), signal, "{mode}");
+            assert_eq!(mode.signal_name(), name, "{mode}");
+        }
+    }
+
+    #[test]
+    fn default_is_fast_within_the_default_timeout() {
+        let shutdown = Shutdown::default();
+        assert_eq!(shutdown.mode, ShutdownMode::Fast);
+        assert_eq!(shutdown.timeout, DEFAULT_SHUTDOWN_TIMEOUT);
+
+        let shutdown = Shutdown::from(ShutdownMode::Immediate);
+        assert_eq!(shutdown.timeout, DEFAULT_SHUTDOWN_TIMEOUT);
+    }
+}
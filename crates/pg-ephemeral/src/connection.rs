@@ -0,0 +1,179 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::common::uri::percent_encode;
+
+/// `sslmode` connection parameter, see the libpq documentation for the semantics
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SslMode {
+    /// Ephemeral servers are started without TLS, so this is the default
+    #[default]
+    Disable,
+    Allow,
+    Prefer,
+    Require,
+    VerifyCa,
+    VerifyFull,
+}
+
+impl SslMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SslMode::Disable => "disable",
+            SslMode::Allow => "allow",
+            SslMode::Prefer => "prefer",
+            SslMode::Require => "require",
+            SslMode::VerifyCa => "verify-ca",
+            SslMode::VerifyFull => "verify-full",
+        }
+    }
+
+    /// Whether the client refuses to connect without TLS
+    #[inline]
+    pub fn requires_tls(&self) -> bool {
+        matches!(
+            self,
+            SslMode::Require | SslMode::VerifyCa | SslMode::VerifyFull
+        )
+    }
+}
+
+impl fmt::Display for SslMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SslMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "disable" => Ok(SslMode::Disable),
+            "allow" => Ok(SslMode::Allow),
+            "prefer" => Ok(SslMode::Prefer),
+            "require" => Ok(SslMode::Require),
+            "verify-ca" => Ok(SslMode::VerifyCa),
+            "verify-full" => Ok(SslMode::VerifyFull),
+            other => Err(format!("invalid sslmode: {other}")),
+        }
+    }
+}
+
+/// Everything a client needs to connect to a running instance, independent of the backend
+///
+/// `host` follows the libpq convention: a value starting with `/` is a unix
+/// domain socket directory instead of a TCP host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub password: Option<String>,
+    pub database: String,
+    /// Directory of the server's unix domain socket, if it listens on one
+    pub socket_dir: Option<PathBuf>,
+    pub ssl_mode: SslMode,
+}
+
+impl ConnectionInfo {
+    /// Whether [`ConnectionInfo::host`] refers to a unix domain socket directory
+    #[inline]
+    pub fn is_socket(&self) -> bool {
+        self.host.starts_with('/')
+    }
+
+    /// Renders a `postgresql://` URI
+    pub fn to_uri(&self) -> String {
+        let mut uri = String::from("postgresql://");
+
+        uri.push_str(&percent_encode(&self.user));
+        if let Some(ref password) = self.password {
+            uri.push(':');
+            uri.push_str(&percent_encode(password));
+        }
+        uri.push('@');
+
+        if self.is_socket() {
+            uri.push('/');
+            uri.push_str(&percent_encode(&self.database));
+            uri.push_str(&format!(
+                "?host={}&port={}&sslmode={}",
+                percent_encode(&self.host),
+                self.port,
+                self.ssl_mode
+            ));
+        } else {
+            // IPv6 addresses need brackets to be told apart from the port
+            let host = match self.host.contains(':') && !self.host.starts_with('[') {
+                true => format!("[{}]", self.host),
+                false => self.host.clone(),
+            };
+
+            uri.push_str(&format!(
+                "{}:{}/{}?sslmode={}",
+                host,
+                self.port,
+                percent_encode(&self.database),
+                self.ssl_mode
+            ));
+        }
+
+        uri
+    }
+
+    /// Renders a libpq keyword/value connection string (`host=... port=...`)
+    pub fn to_keyword_value(&self) -> String {
+        let mut params = vec![
+            ("host", self.host.clone()),
+            ("port", self.port.to_string()),
+            ("user", self.user.clone()),
+        ];
+
+        if let Some(ref password) = self.password {
+            params.push(("password", password.clone()));
+        }
+
+        params.push(("dbname", self.database.clone()));
+        params.push(("sslmode", self.ssl_mode.to_string()));
+
+        params
+            .iter()
+            .map(|(key, value)| format!("{key}={}", quote_keyword_value(value)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// `PG*` environment variables understood by libpq based clients (`psql`, `pg_dump`, ...)
+    pub fn to_env(&self) -> Vec<(&'static str, String)> {
+        let mut env = vec![
+            ("PGHOST", self.host.clone()),
+            ("PGPORT", self.port.to_string()),
+            ("PGUSER", self.user.clone()),
+            ("PGDATABASE", self.database.clone()),
+            ("PGSSLMODE", self.ssl_mode.to_string()),
+        ];
+
+        if let Some(ref password) = self.password {
+            env.push(("PGPASSWORD", password.clone()));
+        }
+
+        env
+    }
+}
+
+/// Single quotes a keyword/value parameter when libpq requires it
+fn quote_keyword_value(value: &str) -> String {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || c == '\'' || c == '\\');
+
+    if !needs_quotes {
+        return value.into();
+    }
+
+    let escaped = value.replace('\\', "\\\\").replace('\'', "\\'");
+    format!("'{escaped}'")
+}
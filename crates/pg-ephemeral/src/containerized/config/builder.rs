@@ -0,0 +1,699 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use super::{
+    ContainerMount, ContainerizedBuilderError, ContainerizedBuilderResult, ContainerizedConfig,
+};
+use crate::common::constants::{
+    CONTAINERIZED_CONTAINER_NAME, CONTAINERIZED_ENV_DB, CONTAINERIZED_ENV_PASSWORD,
+    CONTAINERIZED_ENV_PORT, CONTAINERIZED_ENV_USER, CONTAINERIZED_IMAGE_NAME,
+    CONTAINERIZED_IMAGE_TAG, CONTAINERIZED_INIT_EXTENSIONS, CONTAINERIZED_LABEL_BOOT,
+    CONTAINERIZED_LABEL_FINGERPRINT, CONTAINERIZED_LABEL_HOST, CONTAINERIZED_LABEL_MANAGED,
+    CONTAINERIZED_LABEL_PID, CONTAINERIZED_LABEL_PREFIX, CONTAINERIZED_LABEL_SESSION,
+    CONTAINERIZED_MANAGED_SERVER_CONFIGS, CONTAINERIZED_POLL_INTERVAL,
+    CONTAINERIZED_STARTUP_TIMEOUT, DEFAULT_DB_NAME, DEFAULT_DB_PASSWORD, DEFAULT_DB_PORT,
+    DEFAULT_DB_USER, PG_MAX_IDENT_LEN,
+};
+use crate::common::fingerprint::Fingerprint;
+use crate::common::port::reserve_port;
+use crate::common::session::{boot_id, host_id, session_id};
+use crate::containerized::PgImageTag;
+use crate::{Readiness, Shutdown, ShutdownMode};
+
+static CONTAINER_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Builder for a PostgreSQL instance running inside a docker container.
+///
+/// [`ContainerizedBuilder::build`] validates everything docker or the image
+/// entrypoint would otherwise reject only once the container starts.
+#[derive(Debug, Clone)]
+pub struct ContainerizedBuilder {
+    /// Superuser created by the image entrypoint (`POSTGRES_USER`).
+    pub db_user: String,
+
+    /// Password of [`ContainerizedBuilder::db_user`] (`POSTGRES_PASSWORD`).
+    pub db_password: String,
+
+    /// Database created by the image entrypoint (`POSTGRES_DB`).
+    pub db_name: String,
+
+    /// Port the server listens on inside the container.
+    pub db_port: u16,
+
+    /// Host port the server is published on.
+    /// If `None`, a free port is picked on start and kept across restarts.
+    pub host_port: Option<u16>,
+
+    /// Image to run, e.g. `postgres` or `registry.example.com/postgis/postgis`.
+    pub image_name: String,
+
+    /// Tag of [`ContainerizedBuilder::image_name`].
+    pub image_tag: PgImageTag,
+
+    /// Fixed name of the container. Only one container can use it at a time.
+    /// If `None`, a unique `pg-ephemeral-<session>-<n>` name is generated.
+    pub container_name: Option<String>,
+
+    /// Additional labels of the container, next to the ones identifying
+    /// the creating process (see [`ContainerizedConfig::labels`]).
+    pub labels: HashMap<String, String>,
+
+    /// Additional environment variables of the container
+    /// (e.g. `POSTGRES_INITDB_ARGS`).
+    pub env_vars: HashMap<String, String>,
+
+    /// Runtime settings passed to the server as `postgres -c key=value`
+    /// (e.g. `max_connections`, `shared_preload_libraries`).
+    pub server_configs: HashMap<String, String>,
+
+    /// Additional arguments appended to the `postgres` command line.
+    pub extra_args: Vec<String>,
+
+    /// Host files or directories holding init scripts, see
+    /// [`ContainerizedBuilder::with_init_script`].
+    pub init_scripts: Vec<PathBuf>,
+
+    /// Bind and tmpfs mounts of the container.
+    pub mounts: Vec<ContainerMount>,
+
+    /// Keep the container running after the process exits and re-attach to
+    /// it on the next run, see [`ContainerizedBuilder::with_reuse`].
+    pub reuse: bool,
+
+    /// How long [`Containerized::start`](crate::containerized::Containerized)
+    /// waits for the server to accept connections once the container runs
+    /// and how often it checks. Pulling the image isn't included.
+    pub readiness: Readiness,
+
+    /// How [`Containerized::shutdown`](crate::containerized::Containerized)
+    /// and restarts stop the server. Reused containers are never stopped.
+    pub shutdown: Shutdown,
+}
+
+impl Default for ContainerizedBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContainerizedBuilder {
+    pub fn new() -> Self {
+        Self {
+            db_user: DEFAULT_DB_USER.into(),
+            db_password: DEFAULT_DB_PASSWORD.into(),
+            db_name: DEFAULT_DB_NAME.into(),
+            db_port: DEFAULT_DB_PORT,
+            host_port: None,
+            image_name: CONTAINERIZED_IMAGE_NAME.into(),
+            image_tag: CONTAINERIZED_IMAGE_TAG,
+            container_name: None,
+            labels: HashMap::new(),
+            env_vars: HashMap::new(),
+            server_configs: HashMap::new(),
+            extra_args: Vec::new(),
+            init_scripts: Vec::new(),
+            mounts: Vec::new(),
+            reuse: false,
+            readiness: Readiness::new(CONTAINERIZED_STARTUP_TIMEOUT, CONTAINERIZED_POLL_INTERVAL),
+            shutdown: Shutdown::default(),
+        }
+    }
+
+    #[inline]
+    pub fn with_db_user(mut self, user: impl ToString) -> Self {
+        self.db_user = user.to_string();
+        self
+    }
+
+    #[inline]
+    pub fn with_db_password(mut self, pass: impl ToString) -> Self {
+        self.db_password = pass.to_string();
+        self
+    }
+
+    #[inline]
+    pub fn with_db_name(mut self, db_name: impl ToString) -> Self {
+        self.db_name = db_name.to_string();
+        self
+    }
+
+    /// Port the server listens on inside the container
+    #[inline]
+    pub fn with_db_port(mut self, port: u16) -> Self {
+        self.db_port = port;
+        self
+    }
+
+    /// Publish the server on a fixed host port instead of a random one.
+    /// [`ContainerizedBuilder::build`] reserves it, unless the container is reused
+    #[inline]
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.host_port = Some(port);
+        self
+    }
+
+    #[inline]
+    pub fn with_image(mut self, image_name: impl ToString) -> Self {
+        self.image_name = image_name.to_string();
+        self
+    }
+
+    #[inline]
+    pub fn with_tag(mut self, tag: PgImageTag) -> Self {
+        self.image_tag = tag;
+        self
+    }
+
+    /// Same as [`ContainerizedBuilder::with_tag`], for tags coming from
+    /// config files or the command line
+    pub fn with_tag_str(self, tag: &str) -> ContainerizedBuilderResult<Self> {
+        Ok(self.with_tag(tag.parse()?))
+    }
+
+    /// Use a fixed container name instead of a generated unique one, e.g. to
+    /// find the container from outside. Parallel instances with the same
+    /// name conflict
+    #[inline]
+    pub fn with_container_name(mut self, name: impl ToString) -> Self {
+        self.container_name = Some(name.to_string());
+        self
+    }
+
+    #[inline]
+    pub fn with_label(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.labels.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    #[inline]
+    pub fn with_env(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.env_vars.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    #[inline]
+    pub fn with_config_param(mut self, key: &str, value: &str) -> Self {
+        let _old = self.server_configs.insert(key.into(), value.into());
+        #[cfg(feature = "tracing")]
+        if let Some(old) = _old {
+            tracing::warn!(%key, old_value = %old, new_value = %value, "overriding the server config param");
+        }
+        self
+    }
+
+    #[inline]
+    pub fn with_arg(mut self, arg: impl ToString) -> Self {
+        self.extra_args.push(arg.to_string());
+        self
+    }
+
+    /// Seed the database from a `.sql`, `.sql.gz`, `.sql.xz`, `.sql.zst` or
+    /// `.sh` file, or from every such file inside a directory (sorted by name).
+    ///
+    /// The scripts are copied into `/docker-entrypoint-initdb.d` and run by
+    /// the image entrypoint in the order they were added, before the server
+    /// accepts connections. A failing script fails [`crate::Ephemeral::start`]
+    #[inline]
+    pub fn with_init_script(mut self, path: impl AsRef<Path>) -> Self {
+        self.init_scripts.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Mount the host directory or file `host_path` at `container_path`
+    #[inline]
+    pub fn with_bind_mount(mut self, host_path: impl AsRef<Path>, container_path: &str) -> Self {
+        self.mounts.push(ContainerMount::Bind {
+            host_path: host_path.as_ref().to_path_buf(),
+            container_path: container_path.into(),
+            read_only: false,
+        });
+        self
+    }
+
+    /// Same as [`ContainerizedBuilder::with_bind_mount`], without write access
+    #[inline]
+    pub fn with_readonly_bind_mount(
+        mut self,
+        host_path: impl AsRef<Path>,
+        container_path: &str,
+    ) -> Self {
+        self.mounts.push(ContainerMount::Bind {
+            host_path: host_path.as_ref().to_path_buf(),
+            container_path: container_path.into(),
+            read_only: true,
+        });
+        self
+    }
+
+    /// Mount a tmpfs at `container_path`, e.g. the data directory for speed
+    #[inline]
+    pub fn with_tmpfs(mut self, container_path: &str) -> Self {
+        self.mounts.push(ContainerMount::Tmpfs {
+            container_path: container_path.into(),
+        });
+        self
+    }
+
+    /// Give up on a server not accepting connections after `timeout`
+    #[inline]
+    pub fn with_startup_timeout(mut self, timeout: Duration) -> Self {
+        self.readiness = self.readiness.with_timeout(timeout);
+        self
+    }
+
+    /// Check whether the server accepts connections every `interval` during startup
+    #[inline]
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.readiness = self.readiness.with_poll_interval(interval);
+        self
+    }
+
+    /// Stop the server in `mode` on shutdown, see [`ShutdownMode`]
+    #[inline]
+    pub fn with_shutdown_mode(mut self, mode: ShutdownMode) -> Self {
+        self.shutdown = self.shutdown.with_mode(mode);
+        self
+    }
+
+    /// Kill a server still running `timeout` after it was asked to stop
+    #[inline]
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown = self.shutdown.with_timeout(timeout);
+        self
+    }
+
+    /// Keep the container alive after the process exits, the next run with an
+    /// identical config re-attaches to it instead of starting a new one. A
+    /// stopped one, e.g. after a docker restart, is started again.
+    ///
+    /// The container is named after a fingerprint of the config (image, tag,
+    /// credentials, env, settings and labels) unless a fixed name is set.
+    /// [`ContainerizedConfig::db_name`] is recreated from a template taken on
+    /// the first run every time the container is attached, so each run starts
+    /// with the same pristine database. Reuse is meant for consecutive runs,
+    /// concurrent processes sharing one container reset each other's data.
+    /// Reused containers are never removed on shutdown. Once they aren't needed
+    /// anymore, [`crate::reaper::Reaper::with_reused`] (`pg-ephemeral reap
+    /// --reused`) removes those of this machine
+    #[inline]
+    pub fn with_reuse(mut self) -> Self {
+        self.reuse = true;
+        self
+    }
+
+    pub fn build(mut self) -> ContainerizedBuilderResult<ContainerizedConfig> {
+        // credentials
+        validate_identifier("user name", &self.db_user)?;
+        validate_identifier("database name", &self.db_name)?;
+        if self.db_password.is_empty() || self.db_password.contains('\0') {
+            return Err(ContainerizedBuilderError::InvalidPassword);
+        }
+
+        // image
+        validate_image_name(&self.image_name)?;
+        // `Custom` tags may be constructed without going through `FromStr`
+        self.image_tag.as_str().parse::<PgImageTag>()?;
+
+        // files
+        self.init_scripts = self.init_script_files()?;
+        self.mounts = self.resolve_mounts()?;
+
+        // labels
+        if let Some(key) = self
+            .labels
+            .keys()
+            .find(|key| key.starts_with(CONTAINERIZED_LABEL_PREFIX))
+        {
+            return Err(ContainerizedBuilderError::ReservedLabel(key.clone()));
+        }
+
+        // reused containers are found again through a stable name and labels,
+        // per-process ones get a unique name and their creator recorded
+        let container_name = if self.reuse {
+            let fingerprint = self.fingerprint();
+            self.labels.extend([
+                (CONTAINERIZED_LABEL_MANAGED.into(), "true".into()),
+                (CONTAINERIZED_LABEL_FINGERPRINT.into(), fingerprint.clone()),
+                (CONTAINERIZED_LABEL_HOST.into(), host_id().into()),
+            ]);
+
+            self.container_name
+                .take()
+                .unwrap_or_else(|| format!("{CONTAINERIZED_CONTAINER_NAME}-reuse-{fingerprint}"))
+        } else {
+            self.labels.extend([
+                (CONTAINERIZED_LABEL_MANAGED.into(), "true".into()),
+                (
+                    CONTAINERIZED_LABEL_PID.into(),
+                    std::process::id().to_string(),
+                ),
+                (CONTAINERIZED_LABEL_SESSION.into(), session_id().into()),
+                (CONTAINERIZED_LABEL_HOST.into(), host_id().into()),
+            ]);
+            if let Some(boot_id) = boot_id() {
+                self.labels
+                    .insert(CONTAINERIZED_LABEL_BOOT.into(), boot_id.into());
+            }
+
+            self.container_name
+                .take()
+                .unwrap_or_else(unique_container_name)
+        };
+        validate_container_name(&container_name)?;
+
+        // host port, a reused container may already publish it
+        let port_reservation = match self.host_port {
+            Some(port) if !self.reuse => match reserve_port(port)? {
+                Some(reservation) => Some(Arc::new(reservation)),
+                None => return Err(ContainerizedBuilderError::PortInUse(port)),
+            },
+            _ => None,
+        };
+
+        // settings
+        self.validate_env_vars()?;
+        self.validate_server_configs()?;
+
+        Ok(ContainerizedConfig {
+            db_user: self.db_user,
+            db_pass: self.db_password,
+            db_name: self.db_name,
+            db_port: self.db_port,
+            host_port: self.host_port,
+            image_name: self.image_name,
+            image_tag: self.image_tag,
+            container_name,
+            labels: self.labels,
+            env_vars: self.env_vars,
+            server_configs: self.server_configs,
+            extra_args: self.extra_args,
+            init_scripts: self.init_scripts,
+            mounts: self.mounts,
+            reuse: self.reuse,
+            readiness: self.readiness,
+            shutdown: self.shutdown,
+            port_reservation,
+        })
+    }
+
+    /// [`ContainerizedBuilder::init_scripts`] with directories expanded
+    fn init_script_files(&self) -> ContainerizedBuilderResult<Vec<PathBuf>> {
+        let mut files = Vec::new();
+
+        for path in &self.init_scripts {
+            if !std::fs::exists(path)? {
+                return Err(ContainerizedBuilderError::InitScriptNotFound(path.clone()));
+            }
+
+            if !path.is_dir() {
+                if !is_init_script(path) {
+                    return Err(ContainerizedBuilderError::UnsupportedInitScript(
+                        path.clone(),
+                    ));
+                }
+
+                files.push(path.clone());
+                continue;
+            }
+
+            let mut entries = Vec::new();
+            for entry in std::fs::read_dir(path)? {
+                let entry = entry?;
+                if entry.file_type()?.is_file() && is_init_script(&entry.path()) {
+                    entries.push(entry.path());
+                }
+            }
+
+            entries.sort();
+            files.extend(entries);
+        }
+
+        Ok(files)
+    }
+
+    /// Docker silently creates missing bind sources as empty directories and
+    /// only accepts absolute paths
+    fn resolve_mounts(&self) -> ContainerizedBuilderResult<Vec<ContainerMount>> {
+        let mut mounts = Vec::with_capacity(self.mounts.len());
+
+        for mount in &self.mounts {
+            let (ContainerMount::Bind { container_path, .. }
+            | ContainerMount::Tmpfs { container_path }) = mount;
+
+            if !container_path.starts_with('/') {
+                return Err(ContainerizedBuilderError::InvalidMountTarget(
+                    container_path.clone(),
+                ));
+            }
+
+            let mount = match mount {
+                ContainerMount::Bind {
+                    host_path,
+                    container_path,
+                    read_only,
+                } => {
+                    if !std::fs::exists(host_path)? {
+                        return Err(ContainerizedBuilderError::MountSourceNotFound(
+                            host_path.clone(),
+                        ));
+                    }
+
+                    ContainerMount::Bind {
+                        host_path: std::fs::canonicalize(host_path)?,
+                        container_path: container_path.clone(),
+                        read_only: *read_only,
+                    }
+                }
+                tmpfs => tmpfs.clone(),
+            };
+
+            mounts.push(mount);
+        }
+
+        Ok(mounts)
+    }
+
+    /// Hash of everything that ends up in the container, two configs with the
+    /// same fingerprint can share a container. Stable across processes and
+    /// Rust releases, later runs have to find the container again
+    fn fingerprint(&self) -> String {
+        fn update_map(fingerprint: &mut Fingerprint, map: &HashMap<String, String>) {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort();
+
+            fingerprint.update(entries.len().to_le_bytes());
+            for (key, value) in entries {
+                fingerprint.update(key);
+                fingerprint.update(value);
+            }
+        }
+
+        let mut fingerprint = Fingerprint::new();
+        fingerprint.update(&self.image_name);
+        fingerprint.update(self.image_tag.as_str());
+        fingerprint.update(&self.db_user);
+        fingerprint.update(&self.db_password);
+        fingerprint.update(&self.db_name);
+        fingerprint.update(self.db_port.to_le_bytes());
+        fingerprint.update_opt(self.host_port.map(u16::to_le_bytes));
+        update_map(&mut fingerprint, &self.env_vars);
+        update_map(&mut fingerprint, &self.server_configs);
+        update_map(&mut fingerprint, &self.labels);
+
+        fingerprint.update(self.extra_args.len().to_le_bytes());
+        self.extra_args
+            .iter()
+            .for_each(|arg| fingerprint.update(arg));
+
+        fingerprint.update(self.mounts.len().to_le_bytes());
+        for mount in &self.mounts {
+            match mount {
+                ContainerMount::Bind {
+                    host_path,
+                    container_path,
+                    read_only,
+                } => {
+                    fingerprint.update("bind");
+                    fingerprint.update(host_path.as_os_str().as_encoded_bytes());
+                    fingerprint.update(container_path);
+                    fingerprint.update([u8::from(*read_only)]);
+                }
+                ContainerMount::Tmpfs { container_path } => {
+                    fingerprint.update("tmpfs");
+                    fingerprint.update(container_path);
+                }
+            }
+        }
+
+        // editing a seed script must not re-attach to a container seeded by the old one
+        fingerprint.update(self.init_scripts.len().to_le_bytes());
+        for script in &self.init_scripts {
+            fingerprint.update(script.as_os_str().as_encoded_bytes());
+            fingerprint.update_opt(std::fs::read(script).ok());
+        }
+
+        fingerprint.hex(16)
+    }
+
+    /// The credentials and port are set from the config, overriding them
+    /// would leave [`crate::ConnectionInfo`] pointing at the wrong values
+    fn validate_env_vars(&self) -> ContainerizedBuilderResult<()> {
+        let managed = [
+            CONTAINERIZED_ENV_PASSWORD,
+            CONTAINERIZED_ENV_USER,
+            CONTAINERIZED_ENV_DB,
+            CONTAINERIZED_ENV_PORT,
+        ];
+
+        match self
+            .env_vars
+            .keys()
+            .find(|key| managed.contains(&key.as_str()))
+        {
+            Some(key) => Err(ContainerizedBuilderError::ReservedEnv(key.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Rejects settings `postgres` would see twice (names are case-insensitive)
+    /// and settings this crate manages itself
+    fn validate_server_configs(&self) -> ContainerizedBuilderResult<()> {
+        let mut seen = HashSet::new();
+
+        for key in self.server_configs.keys() {
+            let name = key.to_lowercase();
+
+            if name.is_empty() || name.contains(['=', ' ']) {
+                return Err(ContainerizedBuilderError::InvalidConfigParam(key.clone()));
+            }
+
+            if CONTAINERIZED_MANAGED_SERVER_CONFIGS.contains(&name.as_str()) {
+                return Err(ContainerizedBuilderError::ReservedConfigParam(key.clone()));
+            }
+
+            if !seen.insert(name) {
+                return Err(ContainerizedBuilderError::DuplicateConfigParam(key.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// `pg-ephemeral-<session>-<n>`, unique across processes and within one
+fn unique_container_name() -> String {
+    format!(
+        "{}-{}-{}",
+        CONTAINERIZED_CONTAINER_NAME,
+        session_id(),
+        CONTAINER_COUNTER.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+fn is_init_script(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+
+    CONTAINERIZED_INIT_EXTENSIONS
+        .iter()
+        .any(|extension| name.ends_with(extension))
+}
+
+/// Role and database names end up as (silently truncated) identifiers
+fn validate_identifier(kind: &'static str, value: &str) -> ContainerizedBuilderResult<()> {
+    if value.is_empty() || value.len() > PG_MAX_IDENT_LEN || value.contains('\0') {
+        return Err(ContainerizedBuilderError::InvalidIdentifier {
+            kind,
+            value: value.into(),
+        });
+    }
+
+    Ok(())
+}
+
+/// `[registry[:port]/]path`, lowercase alphanumerics separated by `.`, `_`, `-` and `/`
+fn validate_image_name(name: &str) -> ContainerizedBuilderResult<()> {
+    let valid_char = |c: char| {
+        c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '_' | '-' | '/' | ':')
+    };
+    let is_separator = |c: char| matches!(c, '.' | '_' | '-' | '/' | ':');
+
+    if name.is_empty()
+        || !name.chars().all(valid_char)
+        || name.starts_with(is_separator)
+        || name.ends_with(is_separator)
+    {
+        return Err(ContainerizedBuilderError::InvalidImageName(name.into()));
+    }
+
+    Ok(())
+}
+
+/// `[a-zA-Z0-9][a-zA-Z0-9_.-]+`, the same rule docker applies
+fn validate_container_name(name: &str) -> ContainerizedBuilderResult<()> {
+    let mut chars = name.chars();
+
+    let valid = name.len() > 1
+        && chars.next().is_some_and(|c| c.is_ascii_alphanumeric())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+
+    match valid {
+        true => Ok(()),
+        false => Err(ContainerizedBuilderError::InvalidContainerName(name.into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprint_is_stable_and_covers_the_config() {
+        let builder = ContainerizedBuilder::new()
+            .with_env("TZ", "UTC")
+            .with_config_param("work_mem", "8MB");
+
+        assert_eq!(builder.fingerprint(), builder.clone().fingerprint());
+        assert_eq!(builder.fingerprint().len(), 16);
+
+        for changed in [
+            builder.clone().with_db_password("other"),
+            builder.clone().with_port(6543),
+            builder.clone().with_env("TZ", "CET"),
+            builder.clone().with_arg("-N"),
+            builder.clone().with_tmpfs("/var/lib/postgresql/data"),
+        ] {
+            assert_ne!(builder.fingerprint(), changed.fingerprint());
+        }
+    }
+
+    #[test]
+    fn reused_containers_are_named_after_the_fingerprint() {
+        let build = || ContainerizedBuilder::new().with_reuse().build().unwrap();
+
+        assert_eq!(build().container_name, build().container_name);
+        assert!(!build().labels.contains_key(CONTAINERIZED_LABEL_PID));
+    }
+
+    #[test]
+    fn build_reserves_the_host_port() {
+        let port = crate::common::port::reserve_free_port().unwrap().port();
+        let config = ContainerizedBuilder::new().with_port(port).build().unwrap();
+        assert!(config.port_reservation.is_some());
+
+        // the first config holds the port until its container published it
+        assert!(matches!(
+            ContainerizedBuilder::new().with_port(port).build(),
+            Err(ContainerizedBuilderError::PortInUse(taken)) if taken == port
+        ));
+
+        // a reused container may be the one publishing it
+        let reused = ContainerizedBuilder::new().with_port(port).with_reuse();
+        assert!(reused.build().unwrap().port_reservation.is_none());
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::builder::ContainerizedBuilder;
+use crate::common::constants::{CONTAINERIZED_INIT_DIR, CONTAINERIZED_PROGRAM_POSTGRES};
+use crate::common::port::PortReservation;
+use crate::containerized::PgImageTag;
+use crate::{Readiness, Shutdown};
+
+#[derive(Debug, Clone)]
+pub struct ContainerizedConfig {
+    pub db_user: String,
+    pub db_pass: String,
+    pub db_name: String,
+    /// Port the server listens on inside the container
+    pub db_port: u16,
+    /// Host port published for [`ContainerizedConfig::db_port`], `None` picks a free one on start
+    pub host_port: Option<u16>,
+    pub image_name: String,
+    pub image_tag: PgImageTag,
+    pub container_name: String,
+    /// Labels of the container. Always holds the creator pid, a random id of
+    /// the creating process and the machine and boot it ran on, which
+    /// identify containers left behind by crashed processes.
+    /// [`crate::Ephemeral::start`] adds the creation time
+    pub labels: HashMap<String, String>,
+    /// Additional environment variables of the container
+    pub env_vars: HashMap<String, String>,
+    /// Runtime settings passed to the server as `postgres -c key=value`
+    pub server_configs: HashMap<String, String>,
+    /// Additional arguments appended to the `postgres` command line
+    pub extra_args: Vec<String>,
+    /// Init scripts (`.sql`, `.sql.gz`, `.sh`, ...) run by the image entrypoint
+    /// on the first start, in this order
+    pub init_scripts: Vec<PathBuf>,
+    pub mounts: Vec<ContainerMount>,
+    /// Keep the container across runs, see [`ContainerizedBuilder::with_reuse`]
+    pub reuse: bool,
+    pub readiness: Readiness,
+    pub shutdown: Shutdown,
+    /// Held until the container published [`ContainerizedConfig::host_port`],
+    /// shared between clones of the config
+    pub(crate) port_reservation: Option<Arc<PortReservation>>,
+}
+
+/// A host directory or a tmpfs mounted into the container
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ContainerMount {
+    Bind {
+        host_path: PathBuf,
+        container_path: String,
+        read_only: bool,
+    },
+    Tmpfs {
+        container_path: String,
+    },
+}
+
+impl ContainerizedConfig {
+    pub fn builder() -> ContainerizedBuilder {
+        ContainerizedBuilder::new()
+    }
+
+    /// Image name and tag as handed to testcontainers, which always joins them
+    /// with a `:`. A digest-only tag moves the `@<algorithm>` into the name,
+    /// so the result still reads `name@sha256:<hex>`
+    pub fn image_reference(&self) -> (String, String) {
+        let tag = self.image_tag.as_str();
+
+        match (self.image_tag.tag(), tag.split_once(':')) {
+            (None, Some((algorithm, hex))) => {
+                (format!("{}@{}", self.image_name, algorithm), hex.into())
+            }
+            _ => (self.image_name.clone(), tag.into()),
+        }
+    }
+
+    /// Targets of [`ContainerizedConfig::init_scripts`] inside the container,
+    /// numbered since the entrypoint runs them sorted by name
+    pub fn init_script_targets(&self) -> Vec<(PathBuf, String)> {
+        self.init_scripts
+            .iter()
+            .enumerate()
+            .map(|(index, script)| {
+                let name = script
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+
+                (
+                    script.clone(),
+                    format!("{CONTAINERIZED_INIT_DIR}/{index:03}-{name}"),
+                )
+            })
+            .collect()
+    }
+
+    /// Container command running `postgres` with the configured settings and
+    /// arguments, empty when there are none so the image default stays in place
+    pub fn command(&self) -> Vec<String> {
+        if self.server_configs.is_empty() && self.extra_args.is_empty() {
+            return Vec::new();
+        }
+
+        let mut configs: Vec<_> = self.server_configs.iter().collect();
+        configs.sort();
+
+        std::iter::once(CONTAINERIZED_PROGRAM_POSTGRES.to_string())
+            .chain(
+                configs
+                    .into_iter()
+                    .flat_map(|(key, value)| ["-c".to_string(), format!("{key}={value}")]),
+            )
+            .chain(self.extra_args.iter().cloned())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_passes_settings_before_the_extra_args() {
+        let config = ContainerizedBuilder::new()
+            .with_config_param("work_mem", "8MB")
+            .with_arg("-N")
+            .with_arg("20")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            config.command(),
+            ["postgres", "-c", "work_mem=8MB", "-N", "20"]
+        );
+    }
+
+    #[test]
+    fn image_reference_moves_a_bare_digest_into_the_name() {
+        let hex = "5bc7d6a1f0b4e8a2c3d9e7f6a5b4c3d2e1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6";
+        let reference = |tag: &str| {
+            ContainerizedBuilder::new()
+                .with_tag_str(tag)
+                .unwrap()
+                .build()
+                .unwrap()
+                .image_reference()
+        };
+
+        assert_eq!(reference("16"), ("postgres".into(), "16".into()));
+        assert_eq!(
+            reference(&format!("17@sha256:{hex}")),
+            ("postgres".into(), format!("17@sha256:{hex}"))
+        );
+        assert_eq!(
+            reference(&format!("sha256:{hex}")),
+            ("postgres@sha256".into(), hex.into())
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ContainerizedBuilderError {
+    #[error("I/O operation failed: {0}")]
+    IOError(#[from] std::io::Error),
+
+    #[error("invalid {kind}: `{value}`")]
+    InvalidIdentifier { kind: &'static str, value: String },
+
+    #[error("the password must be non-empty and must not contain NUL bytes")]
+    InvalidPassword,
+
+    #[error("invalid image name: `{0}`")]
+    InvalidImageName(String),
+
+    #[error("invalid image tag: `{0}`")]
+    InvalidImageTag(String),
+
+    #[error("invalid container name: `{0}`")]
+    InvalidContainerName(String),
+
+    #[error("host port {0} is already in use")]
+    PortInUse(u16),
+
+    #[error("environment variable `{0}` is managed by pg-ephemeral")]
+    ReservedEnv(String),
+
+    #[error("label `{0}` is managed by pg-ephemeral")]
+    ReservedLabel(String),
+
+    #[error("init script does not exist: {0}")]
+    InitScriptNotFound(PathBuf),
+
+    #[error(
+        "unsupported init script, expected `.sql`, `.sql.gz`, `.sql.xz`, `.sql.zst` or `.sh`: {0}"
+    )]
+    UnsupportedInitScript(PathBuf),
+
+    #[error("bind mount source does not exist: {0}")]
+    MountSourceNotFound(PathBuf),
+
+    #[error("mount target must be an absolute path: `{0}`")]
+    InvalidMountTarget(String),
+
+    #[error("invalid server config param: `{0}`")]
+    InvalidConfigParam(String),
+
+    #[error("server config param `{0}` is managed by pg-ephemeral")]
+    ReservedConfigParam(String),
+
+    #[error("server config param `{0}` is set more than once")]
+    DuplicateConfigParam(String),
+}
+
+pub type ContainerizedBuilderResult<T> = std::result::Result<T, ContainerizedBuilderError>;
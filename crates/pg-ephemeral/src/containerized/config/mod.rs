@@ -0,0 +1,10 @@
+mod builder;
+#[allow(clippy::module_inception)]
+mod config;
+mod error;
+
+pub use error::ContainerizedBuilderError;
+use error::ContainerizedBuilderResult;
+
+pub use builder::ContainerizedBuilder;
+pub use config::{ContainerMount, ContainerizedConfig};
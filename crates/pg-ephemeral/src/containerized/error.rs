@@ -1,9 +1,48 @@
+use std::time::Duration;
+
 use testcontainers::TestcontainersError;
 
+use super::config::ContainerizedBuilderError;
+use crate::client::ClientError;
+use crate::template::TemplateError;
+
 #[derive(Debug, thiserror::Error)]
 pub enum ContainerizedError {
+    #[error("failed to construct `ContainerizedConfig`: {0}")]
+    ContainerizedBuilderError(#[from] ContainerizedBuilderError),
+
     #[error("testcontainer error: {0}")]
     TestContainerError(#[from] TestcontainersError),
+
+    #[error("template database failed: {0}")]
+    TemplateError(#[from] TemplateError),
+
+    #[error("database client failed: {0}")]
+    ClientError(#[from] ClientError),
+
+    #[error("init script `{script}` failed:\n{logs}")]
+    InitScriptFailed { script: String, logs: String },
+
+    #[error("host port {0} is already in use")]
+    PortInUse(u16),
+
+    #[error("the restarted container moved from host port {old} to {new}")]
+    PortChanged { old: u16, new: u16 },
+
+    #[error("the container exited during startup:\n{logs}")]
+    ContainerExited { logs: String },
+
+    #[error("the server did not accept connections within {0:?}")]
+    StartupTimeout(Duration),
+
+    #[error("I/O operation failed: {0}")]
+    IOError(#[from] std::io::Error),
+
+    #[error("setting `{0}` is managed by pg-ephemeral or passed on the command line")]
+    ReadOnlyConfigParam(String),
+
+    #[error("the container is not running")]
+    NotRunning,
 }
 
 pub type ContainerizedResult<T> = std::result::Result<T, ContainerizedError>;
@@ -1,17 +1,40 @@
-use testcontainers::core::{WaitFor, wait::LogWaitStrategy};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use testcontainers::bollard::errors::Error as BollardError;
+use testcontainers::bollard::query_parameters::{
+    InspectContainerOptions, RemoveContainerOptions, StartContainerOptions,
+};
+use testcontainers::core::client::{ClientError as DockerError, docker_client_instance};
+use testcontainers::core::{AccessMode, ContainerPort, ExecCommand, Mount, WaitFor};
 use testcontainers::runners::AsyncRunner;
-use testcontainers::{ContainerAsync, GenericImage, ImageExt};
+use testcontainers::{ContainerAsync, GenericImage, ImageExt, ReuseDirective, TestcontainersError};
 
-use super::config::ContainerizedConfig;
+use super::config::{ContainerMount, ContainerizedConfig};
 use super::error::{ContainerizedError, ContainerizedResult};
-use crate::Ephemeral;
+use crate::client::{Client, ClientResult, reload};
 use crate::common::constants::{
-    CONTAINERIZED_ENV_DB, CONTAINERIZED_ENV_PASSWORD, CONTAINERIZED_ENV_USER,
+    CONTAINERIZED_ENV_DB, CONTAINERIZED_ENV_PASSWORD, CONTAINERIZED_ENV_PORT,
+    CONTAINERIZED_ENV_USER, CONTAINERIZED_INIT_DIR, CONTAINERIZED_LABEL_CREATED,
+    CONTAINERIZED_MANAGED_SERVER_CONFIGS, CONTAINERIZED_REUSE_TEMPLATE,
 };
+use crate::common::port::reserve_free_port;
+use crate::common::session::unix_timestamp;
+use crate::common::sql::{quote_ident, quote_literal};
+use crate::template::DatabaseTemplate;
+use crate::{ConnectionInfo, Ephemeral, PingStatus, Shutdown, ShutdownMode, SslMode};
+use crate::{log_debug, log_error, log_warn};
+
+/// Log lines kept in startup errors
+const STARTUP_LOG_LINES: usize = 20;
 
 pub struct Containerized {
     config: ContainerizedConfig,
     container: Option<ContainerAsync<GenericImage>>,
+    /// Host and port docker mapped [`ContainerizedConfig::db_port`] to,
+    /// resolved once [`Containerized::start`] finishes
+    endpoint: Option<(String, u16)>,
 }
 
 impl Containerized {
@@ -19,38 +42,135 @@ impl Containerized {
         Self {
             config,
             container: None,
+            endpoint: None,
         }
     }
+
+    #[inline]
+    pub fn config(&self) -> &ContainerizedConfig {
+        &self.config
+    }
+
+    /// Host the container is reachable on, `None` until started
+    #[inline]
+    pub fn host(&self) -> Option<&str> {
+        self.endpoint.as_ref().map(|(host, _)| host.as_str())
+    }
+
+    /// Host port mapped to the database port inside the container, `None` until started
+    #[inline]
+    pub fn host_port(&self) -> Option<u16> {
+        self.endpoint.as_ref().map(|(_, port)| *port)
+    }
+
+    /// Connection URI for the running container, `None` until started
+    pub fn connection_uri(&self) -> Option<String> {
+        self.connection_info().ok().map(|info| info.to_uri())
+    }
 }
 
 impl Ephemeral<ContainerizedError> for Containerized {
     async fn start(&mut self) -> ContainerizedResult<()> {
-        let default_wait_strategy = WaitFor::log(LogWaitStrategy::stdout(
-            "database system is ready to accept connections",
-        ));
-
-        let container =
-            GenericImage::new(self.config.image_name.clone(), self.config.image_tag.into())
-                .with_exposed_port(self.config.db_port.into())
-                .with_wait_for(default_wait_strategy)
-                .with_container_name(self.config.container_name.clone())
-                .with_env_var(CONTAINERIZED_ENV_PASSWORD, self.config.db_pass.clone())
-                .with_env_var(CONTAINERIZED_ENV_USER, self.config.db_user.clone())
-                .with_env_var(CONTAINERIZED_ENV_DB, self.config.db_name.clone())
-                .start()
-                .await?;
+        let (image_name, image_tag) = self.config.image_reference();
+        let request = GenericImage::new(image_name, image_tag)
+            .with_exposed_port(self.config.db_port.into())
+            // see `wait_until_ready`
+            .with_wait_for(WaitFor::Nothing)
+            .with_container_name(self.config.container_name.clone())
+            .with_env_var(CONTAINERIZED_ENV_PASSWORD, self.config.db_pass.clone())
+            .with_env_var(CONTAINERIZED_ENV_USER, self.config.db_user.clone())
+            .with_env_var(CONTAINERIZED_ENV_DB, self.config.db_name.clone())
+            // the server listens on 5432 unless told otherwise
+            .with_env_var(CONTAINERIZED_ENV_PORT, self.config.db_port.to_string());
+
+        // a port picked by docker only lasts until the container stops, a
+        // restart would publish another one. The reservation keeps other
+        // instances off ours until docker bound it
+        let (host_port, _reservation) = match self.config.host_port {
+            Some(host_port) => (host_port, self.config.port_reservation.take()),
+            None => {
+                let reservation = reserve_free_port()?;
+                (reservation.port(), Some(Arc::new(reservation)))
+            }
+        };
+        let request = request.with_mapped_port(host_port, ContainerPort::Tcp(self.config.db_port));
+
+        let request = request.with_labels(&self.config.labels);
+        // stamped here, a config may be built long before it's started
+        let request = match self.config.reuse {
+            true => request,
+            false => request.with_label(CONTAINERIZED_LABEL_CREATED, unix_timestamp().to_string()),
+        };
+
+        let request = self
+            .config
+            .env_vars
+            .iter()
+            .fold(request, |request, (key, value)| {
+                request.with_env_var(key, value)
+            });
+
+        let command = self.config.command();
+        let request = match command.is_empty() {
+            true => request,
+            false => request.with_cmd(command),
+        };
+
+        let request = self
+            .config
+            .init_script_targets()
+            .into_iter()
+            .fold(request, |request, (source, target)| {
+                request.with_copy_to(target, source)
+            });
+
+        let request = self.config.mounts.iter().fold(request, |request, mount| {
+            request.with_mount(to_mount(mount))
+        });
+
+        let request = match self.config.reuse {
+            true => {
+                revive_reused_container(&self.config).await?;
+                request.with_reuse(ReuseDirective::Always)
+            }
+            false => request,
+        };
+
+        let container = request
+            .start()
+            .await
+            .map_err(|err| port_conflict(err, Some(host_port)))?;
+        let (host, host_port) = wait_until_ready(&container, &self.config).await?;
 
         self.container = Some(container);
+        self.endpoint = Some((host, host_port));
+
+        if self.config.reuse {
+            reset_database(&self.config, self.connection_info()?).await?;
+        }
 
         Ok(())
     }
 
     async fn shutdown(&mut self) -> ContainerizedResult<()> {
+        self.shutdown_with(self.config.shutdown).await
+    }
+
+    /// Stops and removes the container, its data is gone afterwards even after
+    /// an immediate shutdown. A reused one is only detached and keeps running
+    async fn shutdown_with(&mut self, shutdown: Shutdown) -> ContainerizedResult<()> {
         if let Some(ref container) = self.container {
-            container.stop().await?
+            match self.config.reuse {
+                false => stop_container(container, shutdown, &self.config).await?,
+                true if shutdown.mode == ShutdownMode::Immediate => {
+                    log_warn!("a reused container keeps running, skipping the immediate shutdown");
+                }
+                true => {}
+            }
         }
 
         self.container = None;
+        self.endpoint = None;
 
         Ok(())
     }
@@ -62,4 +182,471 @@ impl Ephemeral<ContainerizedError> for Containerized {
             Ok(false)
         }
     }
+
+    /// Restarts the container, the data lives in the container and survives.
+    /// The server stays on its host port, [`ContainerizedError::PortInUse`]
+    /// tells that another process took it in the meantime
+    async fn restart(&mut self) -> ContainerizedResult<()> {
+        let container = self
+            .container
+            .as_ref()
+            .ok_or(ContainerizedError::NotRunning)?;
+        let pinned_port = self.endpoint.as_ref().map(|(_, port)| *port);
+
+        log_debug!(container = %container.id(), "restarting the container");
+
+        stop_container(container, self.config.shutdown, &self.config).await?;
+        self.endpoint = None;
+
+        container
+            .start()
+            .await
+            .map_err(|err| port_conflict(err, pinned_port))?;
+        let (host, host_port) = wait_until_ready(container, &self.config).await?;
+        self.endpoint = Some((host, host_port));
+
+        // only containers published on a port picked by docker, e.g. reused
+        // ones created by an older version, move
+        match pinned_port {
+            Some(old) if old != host_port => Err(ContainerizedError::PortChanged {
+                old,
+                new: host_port,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Calls `pg_reload_conf()`, returns once new sessions see the new configuration
+    async fn reload(&self) -> ContainerizedResult<()> {
+        let info = self.connection_info()?;
+        let readiness = self.config.readiness;
+
+        // the client blocks, keep it off the runtime threads
+        let reload = tokio::task::spawn_blocking(move || -> ContainerizedResult<bool> {
+            let mut client = Client::connect_timeout(&info, readiness.timeout)?;
+            reload(
+                &mut client,
+                readiness.timeout,
+                readiness.poll_interval,
+                |client| Ok(client.execute("SELECT pg_reload_conf()")?),
+            )
+        });
+
+        if !reload.await.map_err(std::io::Error::other)?? {
+            log_warn!("the server did not confirm the configuration reload in time");
+        }
+
+        Ok(())
+    }
+
+    async fn set_config_persistent(&mut self, key: &str, value: &str) -> ContainerizedResult<()> {
+        let name = key.to_lowercase();
+
+        // `-c` on the command line takes precedence over `postgresql.auto.conf`
+        if CONTAINERIZED_MANAGED_SERVER_CONFIGS.contains(&name.as_str())
+            || self
+                .config
+                .server_configs
+                .keys()
+                .any(|config| config.to_lowercase() == name)
+        {
+            return Err(ContainerizedError::ReadOnlyConfigParam(key.into()));
+        }
+
+        let info = self.connection_info()?;
+        let timeout = self.config.readiness.timeout;
+        let (key, value) = (key.to_string(), value.to_string());
+
+        let alter = tokio::task::spawn_blocking(move || -> ContainerizedResult<bool> {
+            Ok(Client::connect_timeout(&info, timeout)?.alter_system(&key, &value)?)
+        });
+
+        match alter.await.map_err(std::io::Error::other)?? {
+            true => self.restart().await,
+            false => self.reload().await,
+        }
+    }
+
+    fn connection_info(&self) -> ContainerizedResult<ConnectionInfo> {
+        let (host, port) = self
+            .endpoint
+            .clone()
+            .ok_or(ContainerizedError::NotRunning)?;
+
+        Ok(ConnectionInfo {
+            host,
+            port,
+            user: self.config.db_user.clone(),
+            password: Some(self.config.db_pass.clone()),
+            database: self.config.db_name.clone(),
+            socket_dir: None,
+            ssl_mode: SslMode::Disable,
+        })
+    }
+
+    async fn execute(&self, sql: &str) -> ContainerizedResult<()> {
+        let info = self.connection_info()?;
+        let sql = sql.to_string();
+
+        // the client blocks, keep it off the runtime threads
+        let executed = tokio::task::spawn_blocking(move || Client::connect(&info)?.execute(&sql));
+
+        Ok(executed.await.map_err(std::io::Error::other)??)
+    }
+
+    async fn execute_file(&self, path: impl AsRef<Path>) -> ContainerizedResult<()> {
+        let info = self.connection_info()?;
+        let path = path.as_ref().to_path_buf();
+
+        let executed = tokio::task::spawn_blocking(move || -> ClientResult<()> {
+            let sql = std::fs::read_to_string(path)?;
+            Client::connect(&info)?.execute(&sql)
+        });
+
+        Ok(executed.await.map_err(std::io::Error::other)??)
+    }
+
+    async fn query_scalar(&self, sql: &str) -> ContainerizedResult<Option<String>> {
+        let info = self.connection_info()?;
+        let sql = sql.to_string();
+
+        let scalar =
+            tokio::task::spawn_blocking(move || Client::connect(&info)?.query_scalar(&sql));
+
+        Ok(scalar.await.map_err(std::io::Error::other)??)
+    }
+
+    async fn create_template<F, SE>(
+        &self,
+        name: &str,
+        setup: F,
+    ) -> ContainerizedResult<DatabaseTemplate>
+    where
+        F: FnOnce(&ConnectionInfo) -> Result<(), SE> + Send + 'static,
+        SE: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let info = self.connection_info()?;
+        let name = name.to_string();
+
+        // the client blocks, keep it off the runtime threads
+        let template =
+            tokio::task::spawn_blocking(move || DatabaseTemplate::prepare(info, &name, setup));
+
+        Ok(template.await.map_err(std::io::Error::other)??)
+    }
+}
+
+impl Drop for Containerized {
+    /// testcontainers removes the container in its own `Drop`, but that one
+    /// panics when no tokio runtime is around. Outside of a runtime, a
+    /// temporary one is spun up to remove the container instead
+    fn drop(&mut self) {
+        let Some(container) = self.container.take() else {
+            return;
+        };
+
+        // testcontainers leaves reused containers alone without needing a runtime
+        if self.config.reuse || tokio::runtime::Handle::try_current().is_ok() {
+            drop(container);
+            return;
+        }
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build();
+
+        match runtime {
+            Ok(runtime) => runtime.block_on(async move {
+                let _id = container.id().to_string();
+
+                if let Err(_err) = container.rm().await {
+                    log_error!(error = %_err, id = %_id, "failed to remove the container on drop");
+                }
+            }),
+            Err(_err) => {
+                log_error!(error = %_err, "no runtime available to remove the container on drop");
+                // leaking is better than panicking inside `Drop`
+                std::mem::forget(container);
+            }
+        }
+    }
+}
+
+fn to_mount(mount: &ContainerMount) -> Mount {
+    match mount {
+        ContainerMount::Bind {
+            host_path,
+            container_path,
+            read_only,
+        } => {
+            let mount = Mount::bind_mount(host_path.to_string_lossy(), container_path);
+            match read_only {
+                true => mount.with_access_mode(AccessMode::ReadOnly),
+                false => mount,
+            }
+        }
+        ContainerMount::Tmpfs { container_path } => Mount::tmpfs_mount(container_path),
+    }
+}
+
+/// testcontainers only reuses running containers, creating a new one fails
+/// while a stopped one holds the name. It is started again when it still
+/// carries the labels of the config, otherwise removed
+async fn revive_reused_container(config: &ContainerizedConfig) -> ContainerizedResult<()> {
+    let docker = docker_client_instance()
+        .await
+        .map_err(TestcontainersError::from)?;
+    let name = &config.container_name;
+
+    let container = match docker
+        .inspect_container(name, None::<InspectContainerOptions>)
+        .await
+    {
+        Ok(container) => container,
+        Err(BollardError::DockerResponseServerError {
+            status_code: 404, ..
+        }) => return Ok(()),
+        Err(err) => {
+            return Err(TestcontainersError::from(DockerError::InspectContainer(err)).into());
+        }
+    };
+
+    if container.state.and_then(|state| state.running) == Some(true) {
+        return Ok(());
+    }
+
+    let labels = container
+        .config
+        .and_then(|container| container.labels)
+        .unwrap_or_default();
+    if config
+        .labels
+        .iter()
+        .all(|(key, value)| labels.get(key) == Some(value))
+    {
+        log_debug!(%name, "starting the stopped reused container");
+
+        match docker
+            .start_container(name, None::<StartContainerOptions>)
+            .await
+        {
+            Ok(()) => return Ok(()),
+            Err(_err) => {
+                log_warn!(error = %_err, %name, "failed to start the stopped reused container, replacing it");
+            }
+        }
+    } else {
+        log_warn!(%name, "a stopped container with other labels holds the name, replacing it");
+    }
+
+    let options = RemoveContainerOptions {
+        force: true,
+        v: true,
+        ..Default::default()
+    };
+    docker
+        .remove_container(name, Some(options))
+        .await
+        .map_err(|err| TestcontainersError::from(DockerError::RemoveContainer(err)))?;
+
+    Ok(())
+}
+
+/// Docker only finds out the fixed host port is taken when it publishes it.
+/// The reservation [`crate::containerized::ContainerizedBuilder::build`] holds
+/// only keeps other pg-ephemeral processes off it, not any other program
+fn port_conflict(err: TestcontainersError, host_port: Option<u16>) -> ContainerizedError {
+    let message = err.to_string();
+
+    match host_port {
+        Some(port)
+            if message.contains("port is already allocated")
+                || message.contains("address already in use") =>
+        {
+            log_error!("host port {port} is already in use: {message}");
+            ContainerizedError::PortInUse(port)
+        }
+        _ => err.into(),
+    }
+}
+
+/// Waits for the server to accept connections, returns the host and port
+/// it's reachable on.
+///
+/// The entrypoint runs the init scripts against a temporary server that only
+/// listens on its unix domain socket, probes through the published port don't
+/// reach it. When an init script fails, the entrypoint exits and the container
+/// stops instead.
+async fn wait_until_ready(
+    container: &ContainerAsync<GenericImage>,
+    config: &ContainerizedConfig,
+) -> ContainerizedResult<(String, u16)> {
+    let readiness = config.readiness;
+    let deadline = Instant::now() + readiness.timeout;
+
+    loop {
+        if !container.is_running().await? {
+            return Err(startup_failure(container).await);
+        }
+
+        let host = container.get_host().await?.to_string();
+        let host_port = container.get_host_port_ipv4(config.db_port).await?;
+
+        let status = readiness
+            .probe_tcp_async(&host, host_port, &config.db_user, &config.db_name)
+            .await;
+
+        if status == PingStatus::Accepting {
+            return Ok((host, host_port));
+        }
+
+        if Instant::now() >= deadline {
+            return Err(ContainerizedError::StartupTimeout(readiness.timeout));
+        }
+
+        tokio::time::sleep(readiness.poll_interval).await;
+    }
+}
+
+/// Signals the postmaster, PID 1 of the official image, to stop in
+/// `shutdown.mode` and kills the container once `shutdown.timeout` passed.
+///
+/// `docker stop` would send the `STOPSIGNAL` of the image instead, which is
+/// always a fast shutdown
+async fn stop_container(
+    container: &ContainerAsync<GenericImage>,
+    shutdown: Shutdown,
+    config: &ContainerizedConfig,
+) -> ContainerizedResult<()> {
+    if !container.is_running().await? {
+        return Ok(());
+    }
+
+    log_debug!(container = %container.id(), mode = %shutdown.mode, "stopping the container");
+
+    // the shell builtin, slim images don't ship a `kill` binary
+    let signal = ExecCommand::new([
+        "sh".to_string(),
+        "-c".to_string(),
+        format!("kill -s {} 1", shutdown.mode.signal_name()),
+    ]);
+
+    // the exec races with the container exiting, only the polling below tells
+    if let Err(_err) = container.exec(signal).await {
+        log_debug!(error = %_err, "failed to signal the postmaster");
+    }
+
+    let deadline = Instant::now() + shutdown.timeout;
+    while container.is_running().await? {
+        if Instant::now() >= deadline {
+            log_warn!(
+                container = %container.id(),
+                mode = %shutdown.mode,
+                "the server ignored the shutdown request, killing the container"
+            );
+
+            // a zero timeout makes docker send `SIGKILL` right away
+            container.stop_with_timeout(Some(0)).await?;
+            break;
+        }
+
+        tokio::time::sleep(config.readiness.poll_interval).await;
+    }
+
+    Ok(())
+}
+
+/// Tells a failed init script apart from any other reason the container exited
+async fn startup_failure(container: &ContainerAsync<GenericImage>) -> ContainerizedError {
+    let stdout = container.stdout_to_vec().await.unwrap_or_default();
+    let stderr = container.stderr_to_vec().await.unwrap_or_default();
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    let stderr = String::from_utf8_lossy(&stderr);
+
+    let logs = stdout.lines().chain(stderr.lines()).collect::<Vec<_>>();
+    let logs = logs[logs.len().saturating_sub(STARTUP_LOG_LINES)..].join("\n");
+
+    // `running /docker-entrypoint-initdb.d/<script>` precedes every script
+    let marker = format!("running {CONTAINERIZED_INIT_DIR}/");
+    let last_script = stdout
+        .lines()
+        .rev()
+        .find_map(|line| line.split_once(&marker).map(|(_, script)| script.trim()));
+
+    match last_script {
+        Some(script) if !stdout.contains("PostgreSQL init process complete") => {
+            ContainerizedError::InitScriptFailed {
+                script: script.into(),
+                logs,
+            }
+        }
+        _ => ContainerizedError::ContainerExited { logs },
+    }
+}
+
+/// Recreates [`ContainerizedConfig::db_name`] of a reused container from the
+/// template taken when the container was new, dropping the data of the last run
+async fn reset_database(
+    config: &ContainerizedConfig,
+    info: ConnectionInfo,
+) -> ContainerizedResult<()> {
+    let timeout = config.readiness.timeout;
+    let database = quote_ident(&config.db_name);
+    let database_name = quote_literal(&config.db_name);
+    let template = quote_ident(CONTAINERIZED_REUSE_TEMPLATE);
+
+    // the maintenance database itself can't be dropped while connected to it
+    let admin = ConnectionInfo {
+        database: match config.db_name.as_str() {
+            "postgres" => "template1".into(),
+            _ => "postgres".into(),
+        },
+        ..info
+    };
+
+    // the client blocks, keep it off the runtime threads
+    let reset = tokio::task::spawn_blocking(move || -> ContainerizedResult<()> {
+        let mut client = Client::connect_timeout(&admin, timeout)?;
+
+        let template_exists = client.query_scalar(&format!(
+            "SELECT 1 FROM pg_database WHERE datname = {}",
+            quote_literal(CONTAINERIZED_REUSE_TEMPLATE)
+        ))?;
+
+        if template_exists.is_none() {
+            log_debug!("taking the template of the reused container");
+
+            client.execute(&format!("CREATE DATABASE {template} TEMPLATE {database}"))?;
+            client.execute(&format!(
+                "ALTER DATABASE {template} WITH IS_TEMPLATE true ALLOW_CONNECTIONS false"
+            ))?;
+        }
+
+        log_debug!("resetting the database of the reused container");
+
+        // `WITH (FORCE)` arrived in PostgreSQL 13, older servers get their
+        // sessions terminated by hand. Reported as e.g. `12.19 (Debian ...)`
+        let force = client
+            .server_version()
+            .and_then(|version| version.split(|c: char| !c.is_ascii_digit()).next())
+            .and_then(|major| major.parse::<u32>().ok())
+            .is_none_or(|major| major >= 13);
+
+        // separate queries, neither statement runs inside a transaction block
+        if force {
+            client.execute(&format!("DROP DATABASE IF EXISTS {database} WITH (FORCE)"))?;
+        } else {
+            client.execute(&format!(
+                "SELECT pg_terminate_backend(pid) FROM pg_stat_activity \
+                 WHERE datname = {database_name} AND pid <> pg_backend_pid()"
+            ))?;
+            client.execute(&format!("DROP DATABASE IF EXISTS {database}"))?;
+        }
+        client.execute(&format!("CREATE DATABASE {database} TEMPLATE {template}"))?;
+
+        Ok(())
+    });
+
+    reset.await.map_err(std::io::Error::other)?
 }
@@ -3,7 +3,9 @@ mod error;
 mod impls;
 mod tag;
 
-pub use config::ContainerizedConfig;
+pub use config::{
+    ContainerMount, ContainerizedBuilder, ContainerizedBuilderError, ContainerizedConfig,
+};
 pub use error::ContainerizedError;
 pub use tag::PgImageTag;
 
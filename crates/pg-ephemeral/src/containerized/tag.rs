@@ -1,31 +1,45 @@
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use super::config::ContainerizedBuilderError;
+
+/// Docker limits tags to 128 characters
+const MAX_TAG_LEN: usize = 128;
+
 macro_rules! define_pg_tags {
     (
         $($variant:ident => $tag:literal),+ $(,)?
     ) => {
-        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        /// Tag of the official `postgres` image, or of any image built alike.
+        ///
+        /// Besides the predefined versions, any tag can be parsed at runtime,
+        /// e.g. `"16-alpine"`, `"17.2-bookworm"`, `"sha256:<hex>"` or
+        /// `"17@sha256:<hex>"`. Tags order by their PostgreSQL version, so
+        /// `tag >= PgImageTag::V16` holds for every 16.x and later tag.
+        #[derive(Debug, Clone)]
         pub enum PgImageTag {
-            Custom(&'static str),
+            Custom(String),
             $($variant,)+
         }
 
-        impl From<PgImageTag> for &'static str {
-            fn from(tag: PgImageTag) -> Self {
-                match tag {
+        impl PgImageTag {
+            pub fn as_str(&self) -> &str {
+                match self {
                     PgImageTag::Custom(tag) => tag,
                     $(
                         PgImageTag::$variant => $tag,
                     )+
                 }
             }
-        }
 
-        impl From<PgImageTag> for String {
-            fn from(tag: PgImageTag) -> Self {
+            /// Predefined variant for `tag`, if any
+            fn known(tag: &str) -> Option<Self> {
                 match tag {
-                    PgImageTag::Custom(tag) => tag.to_string(),
                     $(
-                        PgImageTag::$variant => $tag.to_string(),
+                        $tag => Some(PgImageTag::$variant),
                     )+
+                    _ => None,
                 }
             }
         }
@@ -40,3 +54,263 @@ define_pg_tags! {
     V1611 => "16.11",
     V16 => "16",
 }
+
+impl PgImageTag {
+    /// The tag without its digest, `None` for a digest-only tag
+    pub fn tag(&self) -> Option<&str> {
+        let tag = self.as_str();
+
+        match tag.split_once('@') {
+            Some((tag, _)) => Some(tag),
+            None if is_digest(tag) => None,
+            None => Some(tag),
+        }
+    }
+
+    /// `sha256:<hex>` of `17@sha256:<hex>` or `sha256:<hex>`
+    pub fn digest(&self) -> Option<&str> {
+        let tag = self.as_str();
+
+        match tag.split_once('@') {
+            Some((_, digest)) => Some(digest),
+            None if is_digest(tag) => Some(tag),
+            None => None,
+        }
+    }
+
+    /// Major PostgreSQL version, `17` for `17.2-bookworm`. `None` for tags
+    /// without a version such as `latest` or a bare digest
+    pub fn major(&self) -> Option<u32> {
+        self.version().map(|(major, _)| major)
+    }
+
+    /// Minor PostgreSQL version, `2` for `17.2-bookworm`. `None` for tags
+    /// following the latest minor release, such as `17` or `17-alpine`
+    pub fn minor(&self) -> Option<u32> {
+        self.version().and_then(|(_, minor)| minor)
+    }
+
+    /// Image flavour after the version, `alpine` for `16-alpine`. Tags without
+    /// a version are a flavour of their own, e.g. `alpine` or `latest`
+    pub fn variant(&self) -> Option<&str> {
+        let tag = self.tag()?;
+
+        match self.version() {
+            Some(_) => tag.split_once('-').map(|(_, variant)| variant),
+            None => Some(tag),
+        }
+    }
+
+    /// `(major, minor)` from the leading `<major>[.<minor>]` of the tag
+    fn version(&self) -> Option<(u32, Option<u32>)> {
+        let tag = self.tag()?;
+        let version = tag.split_once('-').map_or(tag, |(version, _)| version);
+
+        let (major, minor) = match version.split_once('.') {
+            Some((major, minor)) => (major, Some(minor)),
+            None => (version, None),
+        };
+
+        let major = parse_number(major)?;
+        let minor = match minor {
+            Some(minor) => Some(parse_number(minor)?),
+            None => None,
+        };
+
+        Some((major, minor))
+    }
+}
+
+impl FromStr for PgImageTag {
+    type Err = ContainerizedBuilderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ContainerizedBuilderError::InvalidImageTag(s.into());
+
+        let (tag, digest) = match s.split_once('@') {
+            Some((tag, digest)) => (Some(tag), Some(digest)),
+            None if is_digest(s) => (None, Some(s)),
+            None => (Some(s), None),
+        };
+
+        if tag.is_some_and(|tag| !is_valid_tag(tag)) || digest.is_some_and(|d| !is_digest(d)) {
+            return Err(invalid());
+        }
+
+        Ok(Self::known(s).unwrap_or_else(|| Self::Custom(s.into())))
+    }
+}
+
+impl std::fmt::Display for PgImageTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<PgImageTag> for String {
+    fn from(tag: PgImageTag) -> Self {
+        match tag {
+            PgImageTag::Custom(tag) => tag,
+            tag => tag.as_str().to_string(),
+        }
+    }
+}
+
+/// Tags compare by their text, so a `Custom("16")` equals `V16`
+impl PartialEq for PgImageTag {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for PgImageTag {}
+
+impl Hash for PgImageTag {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl PartialOrd for PgImageTag {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PgImageTag {
+    /// Orders by version, tags without one come first. A floating `17` sorts
+    /// before `17.0`, the remaining ties are broken by the tag itself
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.version()
+            .cmp(&other.version())
+            .then_with(|| self.as_str().cmp(other.as_str()))
+    }
+}
+
+/// `[A-Za-z0-9_][A-Za-z0-9_.-]{0,127}`
+pub(crate) fn is_valid_tag(tag: &str) -> bool {
+    let mut chars = tag.chars();
+
+    tag.len() <= MAX_TAG_LEN
+        && chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+/// `<algorithm>:<hex>`, e.g. `sha256:...`
+pub(crate) fn is_digest(value: &str) -> bool {
+    value.split_once(':').is_some_and(|(algorithm, hex)| {
+        !algorithm.is_empty()
+            && algorithm
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+            && hex.len() >= 32
+            && hex.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
+
+fn parse_number(value: &str) -> Option<u32> {
+    match value.is_empty() || !value.chars().all(|c| c.is_ascii_digit()) {
+        true => None,
+        false => value.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+
+    use super::*;
+
+    const HEX: &str = "5bc7d6a1f0b4e8a2c3d9e7f6a5b4c3d2e1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6";
+
+    fn hash(tag: &PgImageTag) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        tag.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn from_str_maps_known_tags() {
+        assert_eq!("16".parse::<PgImageTag>().unwrap(), PgImageTag::V16);
+        assert!(matches!("18.1".parse(), Ok(PgImageTag::V181)));
+        assert!(matches!("16.4-alpine".parse(), Ok(PgImageTag::Custom(_))));
+    }
+
+    #[test]
+    fn from_str_parses_versions_and_variants() {
+        let tag: PgImageTag = "16.4-alpine".parse().unwrap();
+        assert_eq!(tag.major(), Some(16));
+        assert_eq!(tag.minor(), Some(4));
+        assert_eq!(tag.variant(), Some("alpine"));
+        assert_eq!(tag.digest(), None);
+
+        let tag: PgImageTag = "latest".parse().unwrap();
+        assert_eq!(tag.major(), None);
+        assert_eq!(tag.variant(), Some("latest"));
+    }
+
+    #[test]
+    fn from_str_parses_digests() {
+        let pinned: PgImageTag = format!("17@sha256:{HEX}").parse().unwrap();
+        assert_eq!(pinned.tag(), Some("17"));
+        assert_eq!(pinned.digest(), Some(format!("sha256:{HEX}").as_str()));
+        assert_eq!(pinned.major(), Some(17));
+
+        let bare: PgImageTag = format!("sha256:{HEX}").parse().unwrap();
+        assert_eq!(bare.tag(), None);
+        assert_eq!(bare.digest(), Some(format!("sha256:{HEX}").as_str()));
+        assert_eq!(bare.major(), None);
+    }
+
+    #[test]
+    fn from_str_rejects_invalid_tags() {
+        let too_long = "a".repeat(MAX_TAG_LEN + 1);
+        let short_digest = format!("17@sha256:{}", &HEX[..16]);
+
+        for tag in [
+            "",
+            "-16",
+            ".16",
+            "16 alpine",
+            "16:alpine",
+            "17@",
+            "17@sha256:xyz",
+            "@sha256:0",
+            &short_digest,
+            &too_long,
+        ] {
+            assert!(
+                matches!(
+                    tag.parse::<PgImageTag>(),
+                    Err(ContainerizedBuilderError::InvalidImageTag(_))
+                ),
+                "{tag}"
+            );
+        }
+    }
+
+    #[test]
+    fn equality_and_hash_follow_the_text() {
+        let custom = PgImageTag::Custom("16".into());
+
+        assert_eq!(custom, PgImageTag::V16);
+        assert_eq!(hash(&custom), hash(&PgImageTag::V16));
+        assert_eq!(custom.cmp(&PgImageTag::V16), Ordering::Equal);
+        assert_ne!(PgImageTag::Custom("16-alpine".into()), PgImageTag::V16);
+    }
+
+    #[test]
+    fn ordering_follows_the_version() {
+        let mut tags: Vec<PgImageTag> = ["17.2", "latest", "16", "17", "16.11", "9.6"]
+            .into_iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+        tags.sort();
+
+        let tags: Vec<_> = tags.iter().map(PgImageTag::as_str).collect();
+        assert_eq!(tags, ["latest", "9.6", "16", "16.11", "17", "17.2"]);
+        assert!("16.4-alpine".parse::<PgImageTag>().unwrap() >= PgImageTag::V16);
+    }
+}
@@ -1,8 +1,69 @@
+use std::path::Path;
 use std::result::Result;
 
+use crate::template::DatabaseTemplate;
+use crate::{ConnectionInfo, Shutdown};
+
 /// main interface for interacting with the application
 pub trait Ephemeral<E: std::error::Error> {
     fn start(&mut self) -> impl Future<Output = Result<(), E>>;
+    /// Stops the server the way the config asks for, see [`Ephemeral::shutdown_with`]
     fn shutdown(&mut self) -> impl Future<Output = Result<(), E>>;
+
+    /// Stops the server in `shutdown.mode`, killing it once `shutdown.timeout`
+    /// passed. [`crate::ShutdownMode::Immediate`] simulates a crash.
+    ///
+    /// What happens to the data depends on the backend: a
+    /// [`crate::local::Local`] keeps its data directory, so the next start
+    /// runs crash recovery on it. A [`crate::containerized::Containerized`]
+    /// removes its container along with the data, and a reused one is only
+    /// detached and keeps running, whatever the mode
+    fn shutdown_with(&mut self, shutdown: Shutdown) -> impl Future<Output = Result<(), E>>;
     fn is_running(&self) -> impl Future<Output = Result<bool, E>>;
+
+    /// Stops and starts the server again, keeping its data
+    fn restart(&mut self) -> impl Future<Output = Result<(), E>>;
+
+    /// Makes the server re-read its configuration files
+    fn reload(&self) -> impl Future<Output = Result<(), E>>;
+
+    /// Persists `key = value` through `ALTER SYSTEM`, then reloads the
+    /// configuration, or restarts the server when the setting requires it.
+    ///
+    /// Settings managed by this crate or passed on the command line with
+    /// `with_config_param` take precedence over `ALTER SYSTEM` and are rejected.
+    fn set_config_persistent(
+        &mut self,
+        key: &str,
+        value: &str,
+    ) -> impl Future<Output = Result<(), E>>;
+
+    /// How to connect to the instance, independent of the backend
+    fn connection_info(&self) -> Result<ConnectionInfo, E>;
+
+    /// Runs `sql` against the database of the running instance. Several
+    /// statements separated by `;` run in a single implicit transaction,
+    /// unless they manage transactions themselves.
+    ///
+    /// Server errors surface as [`crate::client::ClientError::DbError`],
+    /// carrying the `SQLSTATE`, message and position.
+    fn execute(&self, sql: &str) -> impl Future<Output = Result<(), E>>;
+
+    /// Runs the SQL script at `path`, see [`Ephemeral::execute`]. The script
+    /// is sent as a whole, `psql` meta-commands and `COPY FROM STDIN` aren't
+    /// supported. [`crate::client::DbError::line_column`] locates errors in it.
+    fn execute_file(&self, path: impl AsRef<Path>) -> impl Future<Output = Result<(), E>>;
+
+    /// First column of the first row `sql` returns, `None` for no rows or `NULL`
+    fn query_scalar(&self, sql: &str) -> impl Future<Output = Result<Option<String>, E>>;
+
+    /// Prepares a template database on the running instance, see [`DatabaseTemplate::prepare`]
+    fn create_template<F, SE>(
+        &self,
+        name: &str,
+        setup: F,
+    ) -> impl Future<Output = Result<DatabaseTemplate, E>>
+    where
+        F: FnOnce(&ConnectionInfo) -> Result<(), SE> + Send + 'static,
+        SE: Into<Box<dyn std::error::Error + Send + Sync>>;
 }
@@ -4,6 +4,9 @@ use crate::containerized::ContainerizedError;
 #[cfg(feature = "local")]
 use crate::local::LocalError;
 
+use crate::reaper::ReaperError;
+use crate::template::TemplateError;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug, thiserror::Error)]
@@ -18,4 +21,10 @@ pub enum Error {
     #[cfg(feature = "containerized")]
     #[error("containerized error: {0}")]
     ContainerizedError(#[from] ContainerizedError),
+
+    #[error("reaper error: {0}")]
+    ReaperError(#[from] ReaperError),
+
+    #[error("template error: {0}")]
+    TemplateError(#[from] TemplateError),
 }
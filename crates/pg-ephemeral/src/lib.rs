@@ -3,17 +3,10 @@
 #[cfg(all(not(feature = "local"), not(feature = "containerized")))]
 compile_error!("No backend selected. Enable at least one feature: `local` or `containerized`.");
 
-#[cfg(feature = "local")]
-compile_error!(
-    "The `local` backend is still under development. For now, enable only the `containerized` feature."
-);
-
 pub(crate) mod common;
 mod error;
 mod macros;
 
-/// [Containerized] doesn't require any platform specific code
-#[cfg(feature = "local")]
 mod platform;
 
 #[cfg(feature = "local")]
@@ -22,8 +15,17 @@ pub mod local;
 #[cfg(feature = "containerized")]
 pub mod containerized;
 
+pub mod client;
+pub mod reaper;
+pub mod template;
+
+mod connection;
 mod ephemeral;
 
+pub use common::readiness::{PingStatus, Readiness};
+pub use common::shutdown::{Shutdown, ShutdownMode};
+pub use common::uri::UriError;
+pub use connection::{ConnectionInfo, SslMode};
 pub use ephemeral::Ephemeral;
 
 pub use error::Error as EphemeralError;
@@ -0,0 +1,328 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::common::constants::LOCAL_INITDB_CACHE_DIR;
+use crate::common::fingerprint::Fingerprint;
+use crate::log_debug;
+
+/// Environment variables that decide the locale `initdb` picks by default
+const LOCALE_ENV: [&str; 4] = ["LC_ALL", "LC_COLLATE", "LC_CTYPE", "LANG"];
+
+/// Cache of pristine data directories, straight out of `initdb`.
+///
+/// Entries are keyed by everything that influences the result of `initdb`:
+/// the PostgreSQL version, the arguments (including locale and encoding),
+/// the locale environment, the OS user and the superuser credentials. A new PostgreSQL
+/// version or different arguments simply miss the cache.
+///
+/// Restoring copies the directory, using reflinks where the filesystem
+/// supports them. Hardlinks are never used, the server modifies its files in
+/// place and would corrupt the cached copy.
+///
+/// Whoever can write to the cache controls the configuration of every server
+/// restored from it, e.g. an `archive_command` running as the restoring user.
+/// The cache directory and its entries must therefore belong to the current
+/// user and be writable by nobody else, other entries are never restored.
+#[derive(Debug, Clone)]
+pub struct InitdbCache {
+    dir: PathBuf,
+}
+
+/// Inputs of `initdb` making up a cache key
+#[derive(Debug)]
+pub(crate) struct InitdbCacheKey<'a> {
+    pub version: &'a str,
+    pub args: &'a [String],
+    /// OS user owning the files, entries of other users aren't readable
+    pub os_user: &'a str,
+    pub user: &'a str,
+    pub password: &'a str,
+}
+
+impl InitdbCache {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// `$XDG_CACHE_HOME/pg-ephemeral-initdb`, otherwise the per-user
+    /// `<tmp>/pg-ephemeral-initdb-<uid>`
+    pub fn default_location() -> PathBuf {
+        if let Some(dir) = std::env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+            && dir.is_absolute()
+        {
+            return dir.join(LOCAL_INITDB_CACHE_DIR);
+        }
+
+        #[cfg(unix)]
+        {
+            // SAFETY: `geteuid` has no memory safety requirements
+            let uid = unsafe { libc::geteuid() };
+            std::env::temp_dir().join(format!("{LOCAL_INITDB_CACHE_DIR}-{uid}"))
+        }
+
+        // the temp dir is per user already
+        #[cfg(not(unix))]
+        std::env::temp_dir().join(LOCAL_INITDB_CACHE_DIR)
+    }
+
+    #[inline]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Removes every cached data directory
+    pub fn clear(&self) -> io::Result<()> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn key(&self, key: &InitdbCacheKey) -> String {
+        let mut fingerprint = Fingerprint::new();
+        fingerprint.update(key.version);
+        fingerprint.update((key.args.len() as u64).to_le_bytes());
+        key.args.iter().for_each(|arg| fingerprint.update(arg));
+        fingerprint.update(key.os_user);
+        fingerprint.update(key.user);
+        fingerprint.update(key.password);
+
+        for var in LOCALE_ENV {
+            fingerprint.update_opt(std::env::var_os(var).map(|value| value.into_encoded_bytes()));
+        }
+
+        format!("v2-{}", fingerprint.hex(32))
+    }
+
+    /// Copies the cached directory for `key` into `target`, `false` on a cache miss
+    pub(crate) fn restore(&self, key: &str, target: &Path) -> io::Result<bool> {
+        let entry = self.dir.join(key);
+
+        if !entry.is_dir() {
+            return Ok(false);
+        }
+
+        check_private(&self.dir)?;
+        check_private(&entry)?;
+
+        log_debug!(entry = %entry.display(), "restoring the data directory from the initdb cache");
+
+        if let Err(err) = copy_dir(&entry, target) {
+            // leave no half copied data directory behind, `initdb` refuses non-empty ones
+            let _ = fs::remove_dir_all(target);
+            return Err(err);
+        }
+
+        Ok(true)
+    }
+
+    /// Stores `source` under `key`. The entry is copied to a scratch directory
+    /// and renamed into place, so concurrent processes never see partial entries
+    pub(crate) fn store(&self, key: &str, source: &Path) -> io::Result<()> {
+        let entry = self.dir.join(key);
+        if entry.is_dir() {
+            return Ok(());
+        }
+
+        if !self.dir.is_dir() {
+            let mut builder = fs::DirBuilder::new();
+            builder.recursive(true);
+
+            #[cfg(unix)]
+            std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+
+            builder.create(&self.dir)?;
+        }
+
+        // someone else's directory could swap our entry for theirs
+        check_private(&self.dir)?;
+
+        let scratch = self.dir.join(format!(".{key}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&scratch);
+
+        let result = copy_dir(source, &scratch).and_then(|_| fs::rename(&scratch, &entry));
+        if result.is_err() {
+            let _ = fs::remove_dir_all(&scratch);
+
+            // another process won the race, that's fine
+            if entry.is_dir() {
+                return Ok(());
+            }
+        }
+
+        result
+    }
+}
+
+/// Fails unless `path` belongs to the current user and nobody else can write to it
+#[cfg(unix)]
+fn check_private(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = fs::metadata(path)?;
+    // SAFETY: `geteuid` has no memory safety requirements
+    let uid = unsafe { libc::geteuid() };
+
+    if metadata.uid() != uid || metadata.mode() & 0o022 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "`{}` in the initdb cache is not private to the current user",
+                path.display()
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The temp dir and `XDG_CACHE_HOME` are per user, there's no ownership to check
+#[cfg(not(unix))]
+fn check_private(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Recursively copies `from` into the not yet existing `to`, keeping permissions
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir(to)?;
+    fs::set_permissions(to, fs::metadata(from)?.permissions())?;
+
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target = to.join(entry.file_name());
+
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else if file_type.is_file() {
+            copy_file(&entry.path(), &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn copy_file(from: &Path, to: &Path) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    if reflink(from, to).is_ok() {
+        return Ok(());
+    }
+
+    fs::copy(from, to).map(|_| ())
+}
+
+/// Copy-on-write clone of a file (btrfs, XFS, bcachefs, ...)
+#[cfg(target_os = "linux")]
+fn reflink(from: &Path, to: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let source = fs::File::open(from)?;
+    let target = fs::File::options().write(true).create_new(true).open(to)?;
+
+    // SAFETY: both descriptors stay open for the duration of the call
+    if unsafe { libc::ioctl(target.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) } != 0 {
+        let err = io::Error::last_os_error();
+        drop(target);
+        let _ = fs::remove_file(to);
+        return Err(err);
+    }
+
+    target.set_permissions(source.metadata()?.permissions())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache_key() -> InitdbCacheKey<'static> {
+        InitdbCacheKey {
+            version: "postgres (PostgreSQL) 17.5",
+            args: &[],
+            os_user: "tester",
+            user: "pg-user",
+            password: "pg-secret",
+        }
+    }
+
+    fn data_dir(root: &Path) -> PathBuf {
+        let source = root.join("source");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("PG_VERSION"), "17\n").unwrap();
+        source
+    }
+
+    #[test]
+    fn key_depends_on_every_input() {
+        let cache = InitdbCache::new("unused");
+        let key = cache.key(&cache_key());
+
+        assert!(key.starts_with("v2-"));
+        assert_eq!(key, cache.key(&cache_key()));
+
+        let args = ["--encoding=UTF8".to_string()];
+        let other = InitdbCacheKey {
+            args: &args,
+            ..cache_key()
+        };
+        assert_ne!(key, cache.key(&other));
+
+        let other = InitdbCacheKey {
+            password: "other",
+            ..cache_key()
+        };
+        assert_ne!(key, cache.key(&other));
+    }
+
+    #[test]
+    fn store_and_restore() {
+        let root = tempfile::tempdir().unwrap();
+        let cache = InitdbCache::new(root.path().join("cache"));
+        let source = data_dir(root.path());
+
+        let target = root.path().join("target");
+        assert!(!cache.restore("entry", &target).unwrap());
+
+        cache.store("entry", &source).unwrap();
+        assert!(cache.restore("entry", &target).unwrap());
+        assert_eq!(fs::read(target.join("PG_VERSION")).unwrap(), b"17\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restore_rejects_entries_others_can_write() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = tempfile::tempdir().unwrap();
+        let cache = InitdbCache::new(root.path().join("cache"));
+        let source = data_dir(root.path());
+        cache.store("entry", &source).unwrap();
+
+        let entry = cache.dir().join("entry");
+        fs::set_permissions(&entry, fs::Permissions::from_mode(0o777)).unwrap();
+
+        let target = root.path().join("target");
+        let err = cache.restore("entry", &target).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(!target.exists());
+
+        fs::set_permissions(&entry, fs::Permissions::from_mode(0o700)).unwrap();
+        fs::set_permissions(cache.dir(), fs::Permissions::from_mode(0o1777)).unwrap();
+        assert!(cache.restore("entry", &target).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn store_creates_a_private_directory() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = tempfile::tempdir().unwrap();
+        let cache = InitdbCache::new(root.path().join("cache"));
+        cache.store("entry", &data_dir(root.path())).unwrap();
+
+        let mode = fs::metadata(cache.dir()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+}
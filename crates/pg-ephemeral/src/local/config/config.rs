@@ -1,19 +1,39 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 
 use super::builder::LocalBuilder;
+use crate::ConnectionInfo;
 use crate::common::PasswordMethod;
+use crate::common::constants::{DEFAULT_DB_HOST, LOCAL_DATA_DIR, LOCAL_LOG_FILE};
+use crate::common::port::PortReservation;
+use crate::local::{DumpFormat, InitdbCache};
+use crate::{Readiness, Shutdown, SslMode};
 
 #[derive(Debug)]
 pub struct LocalConfig {
     pub db_user: String,
     pub db_pass: PasswordMethod,
     pub db_port: u16,
+    /// Listen on the unix domain socket only, see [`LocalBuilder::with_socket_only`]
+    pub socket_only: bool,
     pub db_name: String,
+    pub ssl_mode: SslMode,
     pub persist: bool,
+    pub server_configs: HashMap<String, String>,
+    pub initdb_args: HashMap<String, String>,
     pub dump_path: Option<PathBuf>,
+    pub dump_format: DumpFormat,
+    pub load_path: Option<PathBuf>,
     pub temp_dir: TempDir,
     pub bin_base_path: PathBuf,
+    pub initdb_cache: Option<InitdbCache>,
+    pub readiness: Readiness,
+    pub shutdown: Shutdown,
+    /// See [`LocalBuilder::with_create_unprivileged_user`]
+    pub create_unprivileged_user: bool,
+    /// Held until the server bound [`LocalConfig::db_port`], `None` for an explicitly configured port
+    pub(crate) port_reservation: Option<PortReservation>,
 }
 
 impl LocalConfig {
@@ -21,23 +41,118 @@ impl LocalConfig {
         LocalBuilder::new()
     }
 
+    /// `postgresql://` URI of the server, see [`ConnectionInfo::to_uri`]
+    pub fn connection_uri(&self) -> std::io::Result<String> {
+        Ok(self.connection_info()?.to_uri())
+    }
+
+    /// Connection details of the server described by this config
+    pub fn connection_info(&self) -> std::io::Result<ConnectionInfo> {
+        // the unix domain socket is always created inside the temp dir
+        let socket_dir = cfg!(unix).then(|| self.temp_path().to_path_buf());
+
+        Ok(ConnectionInfo {
+            host: self.client_host(),
+            port: self.db_port,
+            user: self.db_user.clone(),
+            password: Some(self.db_pass.resolve()?),
+            database: self.db_name.clone(),
+            socket_dir,
+            ssl_mode: self.ssl_mode,
+        })
+    }
+
+    /// Host the server is reached on: the socket directory in socket-only
+    /// mode, otherwise the loopback address it listens on. Not `localhost`,
+    /// clients resolving that to `::1` first would fail
+    pub(crate) fn client_host(&self) -> String {
+        match self.socket_only {
+            true => self.temp_path().to_string_lossy().into_owned(),
+            false => DEFAULT_DB_HOST.to_string(),
+        }
+    }
+
+    /// Directory holding the cluster files (`PGDATA`)
+    #[inline]
+    pub fn data_dir(&self) -> PathBuf {
+        self.temp_dir.path().join(LOCAL_DATA_DIR)
+    }
+
+    /// File the postmaster writes its stdout/stderr into
+    #[inline]
+    pub fn log_file(&self) -> PathBuf {
+        self.temp_dir.path().join(LOCAL_LOG_FILE)
+    }
+
+    /// Root of the temporary directory, also used for the unix domain socket
+    #[inline]
+    pub fn temp_path(&self) -> &Path {
+        self.temp_dir.path()
+    }
+
+    /// [`LocalConfig::initdb_args`] as command-line arguments, sorted by key
+    pub fn initdb_command_args(&self) -> Vec<String> {
+        let mut args: Vec<_> = self.initdb_args.iter().collect();
+        args.sort();
+
+        args.into_iter()
+            .map(|(key, value)| {
+                let key = key.trim_start_matches('-');
+                match (key.len(), value.is_empty()) {
+                    // `-EUTF8`, short options take their value attached
+                    (1, true) => format!("-{key}"),
+                    (1, false) => format!("-{key}{value}"),
+                    (_, true) => format!("--{key}"),
+                    (_, false) => format!("--{key}={value}"),
+                }
+            })
+            .collect()
+    }
+
+    /// [`LocalConfig::server_configs`] as `postgres` command-line arguments, sorted by key
+    pub fn server_command_args(&self) -> Vec<String> {
+        let mut configs: Vec<_> = self.server_configs.iter().collect();
+        configs.sort();
+
+        configs
+            .into_iter()
+            .flat_map(|(key, value)| ["-c".to_string(), format!("{key}={value}")])
+            .collect()
+    }
+
+    /// Full path of a PostgreSQL binary inside [`LocalConfig::bin_base_path`]
     #[inline]
-    pub fn connection_uri(&self) -> String {
-        let uri = match self.db_pass.clone() {
-            PasswordMethod::File { file_path: _ } => {
-                format!(
-                    "postgresql://{}@localhost:{}/{}",
-                    self.db_user, self.db_port, self.db_name
-                )
-            }
-            PasswordMethod::Text(pass) => {
-                format!(
-                    "postgresql://{}:{}@localhost:{}/{}",
-                    self.db_user, pass, self.db_port, self.db_name
-                )
-            }
-        };
-
-        uri
+    pub fn program(&self, name: &str) -> PathBuf {
+        self.bin_base_path
+            .join(format!("{}{}", name, std::env::consts::EXE_SUFFIX))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connection_uri_encodes_reserved_characters() {
+        let config = LocalBuilder::new()
+            .with_db_user("app user")
+            .with_db_password(PasswordMethod::Text("p@ss/w:rd?#".into()))
+            .with_db_name("my/db")
+            .with_db_port(6543)
+            .build()
+            .unwrap();
+
+        let uri = config.connection_uri().unwrap();
+        assert_eq!(
+            uri,
+            format!(
+                "postgresql://app%20user:p%40ss%2Fw%3Ard%3F%23@{DEFAULT_DB_HOST}:6543/my%2Fdb?sslmode=disable"
+            )
+        );
+
+        let parsed = LocalBuilder::from_connection_uri(&uri).unwrap();
+        assert_eq!(parsed.db_user, "app user");
+        assert_eq!(parsed.db_name, "my/db");
+        assert!(matches!(parsed.db_password, PasswordMethod::Text(pass) if pass == "p@ss/w:rd?#"));
     }
 }
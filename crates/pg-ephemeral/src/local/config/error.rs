@@ -1,5 +1,8 @@
 use std::path::PathBuf;
 
+use crate::SslMode;
+use crate::common::uri::UriError;
+
 #[derive(Debug, thiserror::Error)]
 pub enum LocalBuilderError {
     #[error("I/O operation failed: {0}")]
@@ -17,9 +20,39 @@ pub enum LocalBuilderError {
         search_path: PathBuf,
     },
 
+    #[error("invalid server config param: `{0}`")]
+    InvalidConfigParam(String),
+
+    #[error("server config param `{0}` is managed by pg-ephemeral")]
+    ReservedConfigParam(String),
+
+    #[error("server config param `{0}` is set more than once")]
+    DuplicateConfigParam(String),
+
+    #[error("invalid initdb arg: `{0}`")]
+    InvalidInitdbArg(String),
+
+    #[error("initdb arg `{0}` is managed by pg-ephemeral")]
+    ReservedInitdbArg(String),
+
+    #[error("initdb arg `{0}` is set more than once")]
+    DuplicateInitdbArg(String),
+
+    #[error("restore source does not exist: {0}")]
+    LoadPathNotExists(PathBuf),
+
+    #[error("invalid connection URI: {0}")]
+    InvalidConnectionUri(#[from] UriError),
+
     #[error("unable to allocate an available port for the database")]
     DatabasePortFailed,
 
+    #[error("sslmode `{0}` requires TLS, which ephemeral servers don't enable")]
+    TlsUnsupported(SslMode),
+
+    #[error("socket-only mode requires unix domain sockets, which this platform lacks")]
+    SocketUnsupported,
+
     #[error("{0}")]
     Custom(String),
 }
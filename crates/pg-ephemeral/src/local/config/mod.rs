@@ -1,8 +1,10 @@
 mod builder;
+#[allow(clippy::module_inception)]
 mod config;
 mod error;
 
-pub(crate) use error::LocalBuilderError;
+pub use error::LocalBuilderError;
 use error::LocalBuilderResult;
 
+pub use builder::LocalBuilder;
 pub use config::LocalConfig;
@@ -0,0 +1,100 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Output format of the `pg_dump` taken at shutdown, see `pg_dump --format`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// Plain SQL script, restorable with `psql`
+    #[default]
+    Plain,
+    /// Compressed custom archive, restorable with `pg_restore`
+    Custom,
+    /// Directory with one file per table, restorable with `pg_restore`
+    Directory,
+    /// Tar archive, restorable with `pg_restore`
+    Tar,
+}
+
+impl DumpFormat {
+    /// Value of `pg_dump --format`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Plain => "plain",
+            Self::Custom => "custom",
+            Self::Directory => "directory",
+            Self::Tar => "tar",
+        }
+    }
+
+    /// Guesses the format of an existing dump the same way `pg_restore` does:
+    /// directories hold a `toc.dat`, custom archives start with `PGDMP` and
+    /// tar archives carry the `ustar` magic. Everything else is taken as SQL
+    pub fn detect(path: &Path) -> io::Result<Self> {
+        if path.is_dir() {
+            return Ok(Self::Directory);
+        }
+
+        let mut header = Vec::with_capacity(512);
+        File::open(path)?.take(512).read_to_end(&mut header)?;
+
+        if header.starts_with(b"PGDMP") {
+            Ok(Self::Custom)
+        } else if header.get(257..262) == Some(b"ustar") {
+            Ok(Self::Tar)
+        } else {
+            Ok(Self::Plain)
+        }
+    }
+}
+
+impl std::fmt::Display for DumpFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_recognizes_every_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &[u8]| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            path
+        };
+
+        let plain = write("dump.sql", b"--\n-- PostgreSQL database dump\n--\n");
+        assert_eq!(DumpFormat::detect(&plain).unwrap(), DumpFormat::Plain);
+
+        let custom = write("dump.custom", b"PGDMP\x01\x10\x00\x04\x08\x01\x01");
+        assert_eq!(DumpFormat::detect(&custom).unwrap(), DumpFormat::Custom);
+
+        let mut header = vec![0; 512];
+        header[..7].copy_from_slice(b"toc.dat");
+        header[257..263].copy_from_slice(b"ustar\0");
+        let tar = write("dump.tar", &header);
+        assert_eq!(DumpFormat::detect(&tar).unwrap(), DumpFormat::Tar);
+
+        let directory = dir.path().join("dump.dir");
+        std::fs::create_dir(&directory).unwrap();
+        std::fs::write(directory.join("toc.dat"), b"PGDMP").unwrap();
+        assert_eq!(
+            DumpFormat::detect(&directory).unwrap(),
+            DumpFormat::Directory
+        );
+    }
+
+    #[test]
+    fn detect_takes_short_files_as_plain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("empty.sql");
+        std::fs::write(&path, b"").unwrap();
+
+        assert_eq!(DumpFormat::detect(&path).unwrap(), DumpFormat::Plain);
+        assert!(DumpFormat::detect(&dir.path().join("missing")).is_err());
+    }
+}
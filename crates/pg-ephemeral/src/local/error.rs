@@ -1,12 +1,54 @@
+use std::path::PathBuf;
+use std::process::ExitStatus;
+
 use super::config::LocalBuilderError;
+use crate::client::ClientError;
+use crate::template::TemplateError;
 
 #[derive(Debug, thiserror::Error)]
 pub enum LocalError {
     #[error("failed to construct `LocalConfig`: {0}")]
     LocalBuilderError(#[from] LocalBuilderError),
 
+    #[error("I/O operation failed: {0}")]
+    IOError(#[from] std::io::Error),
+
+    #[error("database client failed: {0}")]
+    ClientError(#[from] ClientError),
+
+    #[error("template database failed: {0}")]
+    TemplateError(#[from] TemplateError),
+
     #[error("program not found: {0}")]
     ProgramNotFound(String),
+
+    #[error("`{program}` exited with {status}: {stderr}")]
+    ProgramFailed {
+        program: String,
+        status: ExitStatus,
+        stderr: String,
+    },
+
+    #[error("postgres exited during startup with {status}, see the server log at {log_file}")]
+    ServerExited {
+        status: ExitStatus,
+        log_file: PathBuf,
+    },
+
+    #[error("postgres did not accept connections within {0:?}")]
+    StartupTimeout(std::time::Duration),
+
+    #[error("setting `{0}` is managed by pg-ephemeral or passed on the command line")]
+    ReadOnlyConfigParam(String),
+
+    #[error("postgres is not running")]
+    NotRunning,
+
+    #[error("postgres refuses to run as root, running it as the user `{user}` failed: {source}")]
+    PrivilegeDropFailed {
+        user: String,
+        source: std::io::Error,
+    },
 }
 
 pub type LocalResult<T> = std::result::Result<T, LocalError>;
@@ -1,30 +1,673 @@
-use crate::Ephemeral;
-use crate::platform::sys::Sys;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
 
+use crate::client::{Client, ClientError, reload};
+use crate::common::PasswordMethod;
+use crate::common::constants::{
+    DEFAULT_DB_HOST, LOCAL_MAINTENANCE_DB, LOCAL_MANAGED_SERVER_CONFIGS, LOCAL_PASSWORD_FILE,
+    LOCAL_PORT_RETRIES, LOCAL_PROGRAM_INITDB, LOCAL_PROGRAM_PG_DUMP, LOCAL_PROGRAM_PG_RESTORE,
+    LOCAL_PROGRAM_POSTGRES, LOCAL_PROGRAM_PSQL, LOCAL_UNPRIVILEGED_USER,
+};
+use crate::common::port::reserve_free_port;
+use crate::common::sql::{quote_ident, quote_literal};
+use crate::platform::sys::{ProcessHandle, Signal, SpawnCommand, Sys, SysInfo, SysT};
+use crate::template::DatabaseTemplate;
+use crate::{ConnectionInfo, Ephemeral, PingStatus, Shutdown, SslMode};
+use crate::{log_debug, log_error, log_info, log_warn};
+
+use super::cache::InitdbCacheKey;
 use super::config::LocalConfig;
+use super::dump::DumpFormat;
 use super::error::{LocalError, LocalResult};
 
 pub struct Local {
     config: LocalConfig,
     process: Sys,
+    /// The running postmaster, `None` until [`Local::start`] succeeds
+    postmaster: Option<ProcessHandle>,
 }
 
 impl Local {
-    pub fn new() -> Self {
-        todo!()
+    /// When running as root, `initdb` and `postgres` run as the `postgres`
+    /// user instead, which is given the temp dir. It's only created when
+    /// missing if [`crate::local::LocalBuilder::with_create_unprivileged_user`] asks for it
+    pub fn new(config: LocalConfig) -> LocalResult<Self> {
+        let mut process = Sys::new()?;
+
+        if process.has_root_privilege() {
+            let result = process
+                .drop_privileges(LOCAL_UNPRIVILEGED_USER, config.create_unprivileged_user)
+                .and_then(|_| process.chown(config.temp_path()))
+                // spawning would only report a bare "permission denied" later
+                .and_then(|_| process.check_access(&config.bin_base_path))
+                .and_then(|_| process.check_access(config.temp_path()));
+
+            if let Err(source) = result {
+                return Err(LocalError::PrivilegeDropFailed {
+                    user: LOCAL_UNPRIVILEGED_USER.into(),
+                    source,
+                });
+            }
+
+            log_debug!(
+                user = LOCAL_UNPRIVILEGED_USER,
+                "running postgres unprivileged"
+            );
+        }
+
+        Ok(Self {
+            config,
+            process,
+            postmaster: None,
+        })
+    }
+
+    #[inline]
+    pub fn config(&self) -> &LocalConfig {
+        &self.config
+    }
+
+    #[inline]
+    pub fn connection_uri(&self) -> LocalResult<String> {
+        Ok(self.config.connection_uri()?)
+    }
+
+    /// Effective value of the setting `name` on the running server, as
+    /// reported by `current_setting()`
+    pub fn current_setting(&self, name: &str) -> LocalResult<String> {
+        let value = self
+            .connect(&self.config.db_name)?
+            .query_scalar(&format!("SELECT current_setting({})", quote_literal(name)))?;
+
+        Ok(value.unwrap_or_default())
+    }
+
+    /// Connects to `database` as the superuser through the built-in client.
+    /// Plain text regardless of [`LocalConfig::ssl_mode`], which is meant for
+    /// the users of the server, not for setting it up
+    fn connect(&self, database: &str) -> LocalResult<Client> {
+        let info = ConnectionInfo {
+            database: database.into(),
+            ssl_mode: SslMode::Disable,
+            ..self.config.connection_info()?
+        };
+
+        Ok(Client::connect_timeout(
+            &info,
+            self.config.readiness.timeout,
+        )?)
+    }
+
+    /// Whether `initdb` already ran inside the data directory
+    fn is_initialized(&self) -> bool {
+        self.config.data_dir().join("PG_VERSION").is_file()
+    }
+
+    fn initdb(&self) -> LocalResult<()> {
+        let data_dir = self.config.data_dir();
+        let password = self.config.db_pass.resolve()?;
+
+        // everything except the paths, which differ for every instance
+        let mut args = vec![
+            "--auth-local=trust".to_string(),
+            "--auth-host=scram-sha-256".to_string(),
+        ];
+        args.extend(self.config.initdb_command_args());
+
+        let cache_key = match &self.config.initdb_cache {
+            Some(cache) => {
+                let version = self.postgres_version()?;
+                let key = cache.key(&InitdbCacheKey {
+                    version: &version,
+                    args: &args,
+                    os_user: &self.process.user(),
+                    user: &self.config.db_user,
+                    password: &password,
+                });
+
+                match cache.restore(&key, &data_dir) {
+                    Ok(true) => return Ok(self.process.chown(&data_dir)?),
+                    Ok(false) => log_debug!(key = %key, "initdb cache miss"),
+                    Err(_err) => {
+                        log_warn!(error = %_err, "failed to restore from the initdb cache")
+                    }
+                }
+
+                Some((cache, key))
+            }
+            None => None,
+        };
+
+        // `initdb` only accepts the superuser password through a file, when
+        // running unprivileged it might not be able to read the given one
+        let (pwfile, generated) = match &self.config.db_pass {
+            PasswordMethod::File { file_path } if !self.process.has_root_privilege() => {
+                (file_path.clone(), false)
+            }
+            _ => {
+                let pwfile = self.config.temp_path().join(LOCAL_PASSWORD_FILE);
+                fs::write(&pwfile, &password)?;
+                self.process.chown(&pwfile)?;
+                (pwfile, true)
+            }
+        };
+
+        log_info!(data_dir = %data_dir.display(), "initializing the database cluster");
+
+        let command = SpawnCommand::new(self.config.program(LOCAL_PROGRAM_INITDB))
+            .arg("--pgdata")
+            .arg(&data_dir)
+            .arg("--username")
+            .arg(&self.config.db_user)
+            .arg("--pwfile")
+            .arg(&pwfile)
+            .args(&args)
+            .arg("--no-sync")
+            .unprivileged();
+
+        let result = self.run_program(&command).map(|_| ());
+
+        if generated {
+            fs::remove_file(&pwfile)?;
+        }
+        result?;
+
+        // a broken cache only costs time, never fail the instance for it
+        if let Some((cache, key)) = cache_key
+            && let Err(_err) = cache.store(&key, &data_dir)
+        {
+            log_warn!(error = %_err, "failed to store the data directory in the initdb cache");
+        }
+
+        Ok(())
+    }
+
+    /// Output of `postgres --version`, e.g. `postgres (PostgreSQL) 15.8`
+    fn postgres_version(&self) -> LocalResult<String> {
+        let command =
+            SpawnCommand::new(self.config.program(LOCAL_PROGRAM_POSTGRES)).arg("--version");
+        let child = self.run_program(&command)?;
+
+        Ok(child.stdout().trim().to_string())
+    }
+
+    fn spawn_postmaster(&mut self) -> LocalResult<()> {
+        let command = SpawnCommand::new(self.config.program(LOCAL_PROGRAM_POSTGRES))
+            .arg("-D")
+            .arg(self.config.data_dir())
+            .arg("-p")
+            .arg(self.config.db_port.to_string())
+            .arg("-c")
+            .arg(match self.config.socket_only {
+                true => "listen_addresses=".to_string(),
+                false => format!("listen_addresses={}", DEFAULT_DB_HOST),
+            })
+            .args(self.config.server_command_args())
+            .output_file(self.config.log_file())
+            .unprivileged();
+
+        // the compiled-in socket directory is usually not writable by regular users
+        #[cfg(unix)]
+        let command = command.arg("-k").arg(self.config.temp_path());
+
+        log_debug!(port = self.config.db_port, "spawning the postmaster");
+
+        let postmaster = self
+            .process
+            .spawn(&command)
+            .map_err(|err| program_spawn_error(err, &command))?;
+
+        self.postmaster = Some(postmaster);
+
+        Ok(())
+    }
+
+    /// Spawns the postmaster and waits for it, moving to a freshly reserved
+    /// port when another process bound ours in the meantime
+    fn start_postmaster(&mut self) -> LocalResult<()> {
+        let mut retries = 0;
+
+        loop {
+            self.spawn_postmaster()?;
+
+            match self.wait_until_ready() {
+                Err(LocalError::ServerExited { .. })
+                    if retries < LOCAL_PORT_RETRIES && self.lost_port_race() =>
+                {
+                    retries += 1;
+
+                    let reservation = reserve_free_port()?;
+                    log_warn!(
+                        port = self.config.db_port,
+                        new_port = reservation.port(),
+                        "port was taken by another process, retrying"
+                    );
+
+                    self.postmaster = None;
+                    self.config.db_port = reservation.port();
+                    self.config.port_reservation = Some(reservation);
+                }
+                result => {
+                    // the server owns the port now, or failed for another reason
+                    self.config.port_reservation = None;
+                    return result;
+                }
+            }
+        }
+    }
+
+    /// Whether the postmaster exited because its (reserved) port was already bound
+    fn lost_port_race(&self) -> bool {
+        self.config.port_reservation.is_some()
+            && self.postmaster.as_ref().is_some_and(|postmaster| {
+                postmaster
+                    .stderr()
+                    .contains("could not create any TCP/IP sockets")
+            })
+    }
+
+    /// Blocks until the postmaster accepts connections
+    fn wait_until_ready(&self) -> LocalResult<()> {
+        let Some(ref postmaster) = self.postmaster else {
+            return Ok(());
+        };
+
+        let readiness = self.config.readiness;
+        let deadline = Instant::now() + readiness.timeout;
+
+        loop {
+            if let Some(status) = postmaster.try_status()? {
+                return Err(LocalError::ServerExited {
+                    status,
+                    log_file: self.config.log_file(),
+                });
+            }
+
+            if self.ping(postmaster.pid()) == PingStatus::Accepting {
+                return Ok(());
+            }
+
+            if Instant::now() >= deadline {
+                return Err(LocalError::StartupTimeout(readiness.timeout));
+            }
+
+            std::thread::sleep(readiness.poll_interval);
+        }
+    }
+
+    /// Probes the server through its unix domain socket. It lives inside the
+    /// temp dir, so unlike the TCP port no other process can have grabbed it
+    #[cfg(unix)]
+    fn ping(&self, _pid: u32) -> PingStatus {
+        let socket = self
+            .config
+            .temp_path()
+            .join(format!(".s.PGSQL.{}", self.config.db_port));
+
+        self.config
+            .readiness
+            .probe_socket(&socket, &self.config.db_user, LOCAL_MAINTENANCE_DB)
+    }
+
+    /// Probes the server over TCP, once `postmaster.pid` tells the port belongs
+    /// to our postmaster and not to whatever process grabbed it first
+    #[cfg(not(unix))]
+    fn ping(&self, pid: u32) -> PingStatus {
+        if !self.postmaster_ready(pid) {
+            return PingStatus::NoResponse;
+        }
+
+        let addr = std::net::SocketAddr::new(DEFAULT_DB_HOST, self.config.db_port);
+        self.config
+            .readiness
+            .probe_tcp(addr, &self.config.db_user, LOCAL_MAINTENANCE_DB)
+    }
+
+    /// Checks the status the postmaster publishes in `postmaster.pid`, the same
+    /// thing `pg_ctl` waits on
+    #[cfg(not(unix))]
+    fn postmaster_ready(&self, pid: u32) -> bool {
+        let Ok(content) = fs::read_to_string(self.config.data_dir().join("postmaster.pid")) else {
+            return false;
+        };
+
+        let mut lines = content.lines().map(str::trim);
+        lines.next() == Some(pid.to_string().as_str()) && lines.nth(6) == Some("ready")
+    }
+
+    /// Creates [`LocalConfig::db_name`], `initdb` only provides the maintenance database
+    fn create_database(&self) -> LocalResult<()> {
+        if self.config.db_name == LOCAL_MAINTENANCE_DB {
+            return Ok(());
+        }
+
+        self.connect(LOCAL_MAINTENANCE_DB)?.execute(&format!(
+            "CREATE DATABASE {}",
+            quote_ident(&self.config.db_name)
+        ))?;
+
+        Ok(())
+    }
+
+    /// Restores [`LocalConfig::load_path`] into the database, SQL scripts
+    /// through `psql` and archives through `pg_restore`
+    fn load_data(&self) -> LocalResult<()> {
+        let Some(ref load_path) = self.config.load_path else {
+            return Ok(());
+        };
+
+        let format = DumpFormat::detect(load_path)?;
+        log_info!(path = %load_path.display(), %format, "restoring the database");
+
+        let command = match format {
+            DumpFormat::Plain => self
+                .client_command(LOCAL_PROGRAM_PSQL)?
+                .args(["--no-psqlrc", "--quiet", "--set", "ON_ERROR_STOP=1"])
+                .arg("--dbname")
+                .arg(&self.config.db_name)
+                .arg("--file")
+                .arg(load_path),
+            // roles of the source server usually don't exist here
+            _ => self
+                .client_command(LOCAL_PROGRAM_PG_RESTORE)?
+                .args(["--no-owner", "--exit-on-error"])
+                .arg("--dbname")
+                .arg(&self.config.db_name)
+                .arg(load_path),
+        };
+
+        self.run_program(&command)?;
+
+        Ok(())
+    }
+
+    /// Dumps the database into [`LocalConfig::dump_path`], the server must still be running
+    fn dump_data(&self) -> LocalResult<()> {
+        let Some(ref dump_path) = self.config.dump_path else {
+            return Ok(());
+        };
+
+        log_info!(
+            path = %dump_path.display(),
+            format = %self.config.dump_format,
+            "dumping the database"
+        );
+
+        let command = self
+            .client_command(LOCAL_PROGRAM_PG_DUMP)?
+            .arg("--format")
+            .arg(self.config.dump_format.as_str())
+            .arg("--file")
+            .arg(dump_path)
+            .arg("--dbname")
+            .arg(&self.config.db_name);
+
+        self.run_program(&command)?;
+
+        Ok(())
+    }
+
+    /// A client program (`psql`, `pg_dump`, ...) connecting to this server as the superuser
+    fn client_command(&self, program: &str) -> LocalResult<SpawnCommand> {
+        Ok(SpawnCommand::new(self.config.program(program))
+            .arg("--host")
+            .arg(self.config.client_host())
+            .arg("--port")
+            .arg(self.config.db_port.to_string())
+            .arg("--username")
+            .arg(&self.config.db_user)
+            .arg("--no-password")
+            .env(
+                "PGCONNECT_TIMEOUT",
+                // `0` would wait forever
+                self.config.readiness.timeout.as_secs().max(1).to_string(),
+            )
+            .env("PGPASSWORD", self.config.db_pass.resolve()?))
+    }
+
+    /// Runs a program to completion, surfacing its stderr when it fails
+    fn run_program(&self, command: &SpawnCommand) -> LocalResult<ProcessHandle> {
+        let child = self
+            .process
+            .spawn(command)
+            .map_err(|err| program_spawn_error(err, command))?;
+
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(LocalError::ProgramFailed {
+                program: command.program_name(),
+                status,
+                stderr: child.stderr().trim().to_string(),
+            });
+        }
+
+        Ok(child)
+    }
+
+    /// Stops the postmaster in `shutdown.mode`, falling back to a hard kill
+    /// when it doesn't exit within `shutdown.timeout`
+    fn stop_postmaster(&self, postmaster: &ProcessHandle, shutdown: Shutdown) -> LocalResult<()> {
+        if !postmaster.is_running()? {
+            return Ok(());
+        }
+
+        log_debug!(pid = postmaster.pid(), mode = %shutdown.mode, "stopping the postmaster");
+
+        match self.process.kill(postmaster, shutdown.mode.signal()) {
+            Ok(()) => {}
+            // platforms without signals can only terminate the process
+            Err(err) if err.kind() == std::io::ErrorKind::Unsupported => {
+                self.process.kill(postmaster, Signal::Kill)?
+            }
+            Err(err) => return Err(err)?,
+        }
+
+        if postmaster.wait_timeout(shutdown.timeout)?.is_none() {
+            log_warn!(
+                pid = postmaster.pid(),
+                mode = %shutdown.mode,
+                "postmaster ignored the shutdown request, killing it"
+            );
+
+            self.process.kill(postmaster, Signal::Kill)?;
+            postmaster.wait()?;
+        }
+
+        Ok(())
     }
 }
 
 impl Ephemeral<LocalError> for Local {
     async fn start(&mut self) -> LocalResult<()> {
-        todo!()
+        if self.is_running().await? {
+            return Ok(());
+        }
+
+        let fresh = !self.is_initialized();
+        if fresh {
+            self.initdb()?;
+        }
+
+        let setup = self.start_postmaster().and_then(|_| match fresh {
+            true => self.create_database().and_then(|_| self.load_data()),
+            false => Ok(()),
+        });
+
+        if let Err(err) = setup {
+            // don't leave a half started server behind, nor dump it
+            if let Some(postmaster) = self.postmaster.take() {
+                self.stop_postmaster(&postmaster, self.config.shutdown)?;
+            }
+            return Err(err);
+        }
+
+        log_info!(uri = ?self.config.connection_uri().ok(), "postgres is ready");
+
+        Ok(())
     }
 
     async fn shutdown(&mut self) -> LocalResult<()> {
-        todo!()
+        self.shutdown_with(self.config.shutdown).await
+    }
+
+    /// Dumps the database when configured, then stops the postmaster. The data
+    /// directory stays until the instance is dropped, a later start picks it up
+    async fn shutdown_with(&mut self, shutdown: Shutdown) -> LocalResult<()> {
+        let Some(postmaster) = self.postmaster.take() else {
+            return Ok(());
+        };
+
+        // a failed dump still stops the server
+        let dump = self.dump_data();
+        self.stop_postmaster(&postmaster, shutdown)?;
+
+        dump
     }
 
     async fn is_running(&self) -> LocalResult<bool> {
-        todo!()
+        let Some(ref postmaster) = self.postmaster else {
+            return Ok(false);
+        };
+
+        Ok(postmaster.is_running()?)
+    }
+
+    /// Restarts the postmaster on the same data dir and port, without dumping
+    async fn restart(&mut self) -> LocalResult<()> {
+        let Some(postmaster) = self.postmaster.take() else {
+            return Err(LocalError::NotRunning);
+        };
+
+        log_debug!(port = self.config.db_port, "restarting the postmaster");
+        self.stop_postmaster(&postmaster, self.config.shutdown)?;
+
+        if let Err(err) = self.start_postmaster() {
+            // the start failure is what the caller needs to see
+            if let Some(postmaster) = self.postmaster.take()
+                && let Err(_stop_err) = self.stop_postmaster(&postmaster, self.config.shutdown)
+            {
+                log_warn!(error = %_stop_err, "failed to stop the postmaster after a failed restart");
+            }
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Sends `SIGHUP` to the postmaster, through `pg_reload_conf()` on platforms
+    /// without signals. Returns once new sessions see the new configuration
+    async fn reload(&self) -> LocalResult<()> {
+        let Some(ref postmaster) = self.postmaster else {
+            return Err(LocalError::NotRunning);
+        };
+
+        let mut client = self.connect(&self.config.db_name)?;
+        let readiness = self.config.readiness;
+        let reloaded = reload(
+            &mut client,
+            readiness.timeout,
+            readiness.poll_interval,
+            |client| match self.process.kill(postmaster, Signal::Hup) {
+                Ok(()) => Ok(()),
+                Err(err) if err.kind() == std::io::ErrorKind::Unsupported => {
+                    Ok(client.execute("SELECT pg_reload_conf()")?)
+                }
+                Err(err) => Err(LocalError::IOError(err)),
+            },
+        )?;
+
+        if !reloaded {
+            log_warn!("the server did not confirm the configuration reload in time");
+        }
+
+        Ok(())
+    }
+
+    async fn set_config_persistent(&mut self, key: &str, value: &str) -> LocalResult<()> {
+        let name = key.to_lowercase();
+
+        // `-c` on the command line takes precedence over `postgresql.auto.conf`
+        if LOCAL_MANAGED_SERVER_CONFIGS.contains(&name.as_str())
+            || self
+                .config
+                .server_configs
+                .keys()
+                .any(|config| config.to_lowercase() == name)
+        {
+            return Err(LocalError::ReadOnlyConfigParam(key.into()));
+        }
+
+        if self.postmaster.is_none() {
+            return Err(LocalError::NotRunning);
+        }
+
+        let needs_restart = self
+            .connect(&self.config.db_name)?
+            .alter_system(key, value)?;
+
+        match needs_restart {
+            true => self.restart().await,
+            false => self.reload().await,
+        }
+    }
+
+    fn connection_info(&self) -> LocalResult<ConnectionInfo> {
+        if self.postmaster.is_none() {
+            return Err(LocalError::NotRunning);
+        }
+
+        Ok(self.config.connection_info()?)
+    }
+
+    async fn execute(&self, sql: &str) -> LocalResult<()> {
+        Ok(Client::connect(&self.connection_info()?)?.execute(sql)?)
+    }
+
+    async fn execute_file(&self, path: impl AsRef<Path>) -> LocalResult<()> {
+        let sql = fs::read_to_string(path).map_err(ClientError::from)?;
+        self.execute(&sql).await
+    }
+
+    async fn query_scalar(&self, sql: &str) -> LocalResult<Option<String>> {
+        Ok(Client::connect(&self.connection_info()?)?.query_scalar(sql)?)
+    }
+
+    async fn create_template<F, SE>(&self, name: &str, setup: F) -> LocalResult<DatabaseTemplate>
+    where
+        F: FnOnce(&ConnectionInfo) -> Result<(), SE> + Send + 'static,
+        SE: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Ok(DatabaseTemplate::prepare(
+            self.connection_info()?,
+            name,
+            setup,
+        )?)
+    }
+}
+
+impl Drop for Local {
+    /// Dumps and stops a still running server, e.g. when a test panics before
+    /// calling [`Ephemeral::shutdown`]. The temp dir is removed afterwards by its own
+    /// `Drop`, unless [`LocalConfig::persist`] is set
+    fn drop(&mut self) {
+        let Some(postmaster) = self.postmaster.take() else {
+            return;
+        };
+
+        if let Err(_err) = self.dump_data() {
+            log_error!(error = %_err, "failed to dump the database on drop");
+        }
+
+        if let Err(_err) = self.stop_postmaster(&postmaster, self.config.shutdown) {
+            log_error!(error = %_err, pid = postmaster.pid(), "failed to stop postgres on drop");
+        }
+    }
+}
+
+fn program_spawn_error(err: std::io::Error, command: &SpawnCommand) -> LocalError {
+    match err.kind() {
+        std::io::ErrorKind::NotFound => LocalError::ProgramNotFound(command.program_name()),
+        _ => LocalError::IOError(err),
     }
 }
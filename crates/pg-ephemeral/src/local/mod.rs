@@ -1,6 +1,11 @@
+mod cache;
 pub mod config;
+mod dump;
 mod error;
 mod impls;
 
-pub use error::LocalError;
+pub use cache::InitdbCache;
+pub use config::{LocalBuilder, LocalConfig};
+pub use dump::DumpFormat;
+pub use error::{LocalError, LocalResult};
 pub use impls::Local;
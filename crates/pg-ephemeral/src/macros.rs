@@ -27,3 +27,45 @@ macro_rules! log_warn {
 macro_rules! log_error {
     ($($arg:tt)*) => { tracing::error!($($arg)*) };
 }
+
+// no-op variants, so call sites don't need to be gated behind the `tracing` feature
+
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! log_trace {
+    ($($arg:tt)*) => {
+        ()
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)*) => {
+        ()
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)*) => {
+        ()
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! log_warn {
+    ($($arg:tt)*) => {
+        ()
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)*) => {
+        ()
+    };
+}
@@ -1,4 +1,6 @@
 pub mod sys;
+#[cfg(feature = "local")]
 mod which;
 
+#[cfg(feature = "local")]
 pub use which::{ProgramFinder, ProgramFinderImpl};
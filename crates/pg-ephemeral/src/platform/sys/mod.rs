@@ -1,16 +1,25 @@
 #![allow(dead_code, unused_imports)]
 
 use std::io;
+use std::path::Path;
 
+mod process;
 #[cfg(unix)]
 mod unix;
 #[cfg(windows)]
 mod windows;
 
+pub use process::{ProcessHandle, Signal, SpawnCommand};
+
+#[cfg(unix)]
+pub use unix::{Sys, process_exists, signal_pid};
+#[cfg(windows)]
+pub use windows::{Sys, process_exists, signal_pid};
+
 #[cfg(unix)]
-pub use unix::Sys;
+use unix::send_signal;
 #[cfg(windows)]
-pub use windows::Sys;
+use windows::send_signal;
 
 /// Abstraction for platform-specific system operations required to manage
 /// child processes
@@ -18,11 +27,32 @@ pub trait SysT: SysInfo + Sized {
     /// Initialize and collect all system information needed to spawn a child
     fn new() -> io::Result<Self>;
 
-    /// Spawn a new child process
-    fn spawn();
+    /// Spawn a new child process with its stdout/stderr captured
+    fn spawn(&self, command: &SpawnCommand) -> io::Result<ProcessHandle>;
+
+    /// Deliver `signal` to a child process previously returned by [`SysT::spawn`]
+    fn kill(&self, child: &ProcessHandle, signal: Signal) -> io::Result<()> {
+        child.signal(signal)
+    }
+
+    /// Run children spawned from [`SpawnCommand::unprivileged`] commands as
+    /// `user`, creating it when it doesn't exist yet and `create` is set.
+    /// Only needed, and only possible, while the current process runs as root
+    fn drop_privileges(&mut self, _user: &str, _create: bool) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Fails when the user set up by [`SysT::drop_privileges`] can't reach
+    /// the directory `path`, a no-op without one
+    fn check_access(&self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
 
-    /// Kill a child process
-    fn kill();
+    /// Recursively hands `path` over to the user set up by
+    /// [`SysT::drop_privileges`], a no-op without one
+    fn chown(&self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
 }
 
 /// Provides system information about the current process and environment
@@ -0,0 +1,362 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Amount of the most recent output kept in memory for every captured stream
+const OUTPUT_LIMIT: usize = 64 * 1024;
+
+/// Interval used while polling a child for its exit status
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Signals that can be delivered to a child process
+///
+/// PostgreSQL maps these to its shutdown modes: `Term` is a "smart",
+/// `Int` a "fast" and `Quit` an "immediate" shutdown. `Hup` reloads the
+/// configuration files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Term,
+    Int,
+    Quit,
+    Kill,
+    Hup,
+}
+
+/// Description of a child process to spawn
+#[derive(Debug, Clone)]
+pub struct SpawnCommand {
+    program: PathBuf,
+    args: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    current_dir: Option<PathBuf>,
+    output_file: Option<PathBuf>,
+    unprivileged: bool,
+}
+
+impl SpawnCommand {
+    pub fn new(program: impl AsRef<Path>) -> Self {
+        Self {
+            program: program.as_ref().to_path_buf(),
+            args: Vec::new(),
+            envs: Vec::new(),
+            current_dir: None,
+            output_file: None,
+            unprivileged: false,
+        }
+    }
+
+    #[inline]
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    #[inline]
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    #[inline]
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.envs.push((key.into(), value.into()));
+        self
+    }
+
+    #[inline]
+    pub fn current_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.current_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Append everything the child writes to stdout/stderr to `path` instead
+    /// of capturing it through pipes. Needed for children that leave
+    /// processes of their own behind, e.g. the backends of the postmaster,
+    /// which would otherwise keep the pipes open
+    #[inline]
+    pub fn output_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.output_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Run the child as the user set up by [`super::SysT::drop_privileges`],
+    /// for programs refusing to run as root
+    #[inline]
+    pub fn unprivileged(mut self) -> Self {
+        self.unprivileged = true;
+        self
+    }
+
+    #[inline]
+    pub fn is_unprivileged(&self) -> bool {
+        self.unprivileged
+    }
+
+    #[inline]
+    pub fn program(&self) -> &Path {
+        &self.program
+    }
+
+    /// File name of the program, used for error reporting
+    pub fn program_name(&self) -> String {
+        self.program
+            .file_stem()
+            .unwrap_or(self.program.as_os_str())
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub(super) fn to_command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .envs(self.envs.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        if let Some(ref dir) = self.current_dir {
+            command.current_dir(dir);
+        }
+
+        command
+    }
+}
+
+/// Tail of a captured output stream
+#[derive(Debug, Default)]
+struct OutputBuffer {
+    data: Vec<u8>,
+}
+
+impl OutputBuffer {
+    fn push(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+
+        if self.data.len() > OUTPUT_LIMIT {
+            let excess = self.data.len() - OUTPUT_LIMIT;
+            self.data.drain(..excess);
+        }
+    }
+}
+
+/// Handle to a spawned child process
+///
+/// The child's stdout and stderr are either drained by background threads,
+/// so a chatty process never blocks on a full pipe, or written straight into
+/// the output file of its [`SpawnCommand`].
+#[derive(Debug)]
+pub struct ProcessHandle {
+    pid: u32,
+    program: String,
+    child: Mutex<Child>,
+    output: Output,
+}
+
+/// Where the output of a child ends up
+#[derive(Debug)]
+enum Output {
+    /// Drained from pipes by background threads
+    Captured {
+        stdout: Arc<Mutex<OutputBuffer>>,
+        stderr: Arc<Mutex<OutputBuffer>>,
+        readers: Mutex<Vec<JoinHandle<()>>>,
+    },
+    /// Appended to `path` by the child itself, starting at `offset`
+    File { path: PathBuf, offset: u64 },
+}
+
+impl ProcessHandle {
+    pub(super) fn spawn(command: &SpawnCommand, mut raw: Command) -> io::Result<Self> {
+        if let Some(ref path) = command.output_file {
+            let file = File::options().create(true).append(true).open(path)?;
+            let offset = file.metadata()?.len();
+
+            raw.stdout(Stdio::from(file.try_clone()?))
+                .stderr(Stdio::from(file));
+            let child = raw.spawn()?;
+
+            return Ok(Self {
+                pid: child.id(),
+                program: command.program_name(),
+                child: Mutex::new(child),
+                output: Output::File {
+                    path: path.clone(),
+                    offset,
+                },
+            });
+        }
+
+        let mut child = raw.spawn()?;
+
+        let stdout = Arc::new(Mutex::new(OutputBuffer::default()));
+        let stderr = Arc::new(Mutex::new(OutputBuffer::default()));
+
+        let mut readers = Vec::with_capacity(2);
+        if let Some(pipe) = child.stdout.take() {
+            readers.push(spawn_reader(pipe, stdout.clone())?);
+        }
+        if let Some(pipe) = child.stderr.take() {
+            readers.push(spawn_reader(pipe, stderr.clone())?);
+        }
+
+        Ok(Self {
+            pid: child.id(),
+            program: command.program_name(),
+            child: Mutex::new(child),
+            output: Output::Captured {
+                stdout,
+                stderr,
+                readers: Mutex::new(readers),
+            },
+        })
+    }
+
+    #[inline]
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    #[inline]
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Deliver `signal` to the child
+    pub fn signal(&self, signal: Signal) -> io::Result<()> {
+        let mut child = self.child();
+
+        // never signal a pid that might already be reused by someone else
+        if child.try_wait()?.is_some() {
+            return Ok(());
+        }
+
+        super::send_signal(&mut child, signal)
+    }
+
+    /// Exit status of the child, `None` while it is still running. Never
+    /// blocks, captured output may still be in flight until [`ProcessHandle::wait`]
+    pub fn try_status(&self) -> io::Result<Option<ExitStatus>> {
+        self.child().try_wait()
+    }
+
+    #[inline]
+    pub fn is_running(&self) -> io::Result<bool> {
+        Ok(self.try_status()?.is_none())
+    }
+
+    /// Waits up to `timeout` for the child to exit
+    pub fn wait_timeout(&self, timeout: Duration) -> io::Result<Option<ExitStatus>> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if let Some(status) = self.try_status()? {
+                return Ok(Some(status));
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+
+            std::thread::sleep(WAIT_POLL_INTERVAL.min(deadline - now));
+        }
+    }
+
+    /// Blocks until the child exits and its captured output is complete
+    pub fn wait(&self) -> io::Result<ExitStatus> {
+        let status = self.child().wait()?;
+        self.join_readers();
+        Ok(status)
+    }
+
+    /// Everything the child wrote to stdout so far (bounded to the most recent
+    /// output). With an output file, stdout and stderr are interleaved in it
+    pub fn stdout(&self) -> String {
+        match self.output {
+            Output::Captured { ref stdout, .. } => read_buffer(stdout),
+            Output::File { ref path, offset } => read_file_tail(path, offset),
+        }
+    }
+
+    /// Everything the child wrote to stderr so far, see [`ProcessHandle::stdout`]
+    pub fn stderr(&self) -> String {
+        match self.output {
+            Output::Captured { ref stderr, .. } => read_buffer(stderr),
+            Output::File { ref path, offset } => read_file_tail(path, offset),
+        }
+    }
+
+    fn child(&self) -> MutexGuard<'_, Child> {
+        self.child.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Makes sure the whole output is captured once the child is gone
+    fn join_readers(&self) {
+        let Output::Captured { ref readers, .. } = self.output else {
+            return;
+        };
+
+        let readers = std::mem::take(&mut *readers.lock().unwrap_or_else(PoisonError::into_inner));
+
+        for reader in readers {
+            let _ = reader.join();
+        }
+    }
+}
+
+fn spawn_reader(
+    mut pipe: impl Read + Send + 'static,
+    buffer: Arc<Mutex<OutputBuffer>>,
+) -> io::Result<JoinHandle<()>> {
+    std::thread::Builder::new()
+        .name("pg-ephemeral-output".into())
+        .spawn(move || {
+            let mut chunk = [0u8; 8192];
+
+            loop {
+                let read = match pipe.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(read) => read,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(_) => break,
+                };
+
+                buffer
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(&chunk[..read]);
+            }
+        })
+}
+
+fn read_buffer(buffer: &Mutex<OutputBuffer>) -> String {
+    let buffer = buffer.lock().unwrap_or_else(PoisonError::into_inner);
+    String::from_utf8_lossy(&buffer.data).into_owned()
+}
+
+/// What the child appended to `path` since `offset`, bounded like the captured output
+fn read_file_tail(path: &Path, offset: u64) -> String {
+    let read = || -> io::Result<Vec<u8>> {
+        let mut file = File::open(path)?;
+        let len = file.metadata()?.len();
+        file.seek(SeekFrom::Start(
+            offset.max(len.saturating_sub(OUTPUT_LIMIT as u64)),
+        ))?;
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        Ok(data)
+    };
+
+    String::from_utf8_lossy(&read().unwrap_or_default()).into_owned()
+}
@@ -1,7 +1,12 @@
 use libc::{c_char, geteuid, gethostname, getpwuid};
-use std::{ffi::CStr, io};
+use std::ffi::{CStr, CString};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::{fs, io, mem, ptr};
 
-use super::{SysInfo, SysT};
+use super::{ProcessHandle, Signal, SpawnCommand, SysInfo, SysT};
 
 #[derive(Debug)]
 pub struct Sys {
@@ -13,6 +18,17 @@ pub struct Sys {
 
     /// Checking whether the user is root
     is_root: bool,
+
+    /// Account unprivileged commands run as, only set up while running as root
+    unprivileged: Option<Account>,
+}
+
+/// A user account, see [`SysT::drop_privileges`]
+#[derive(Debug, Clone)]
+struct Account {
+    name: String,
+    uid: u32,
+    gid: u32,
 }
 
 impl SysT for Sys {
@@ -25,12 +41,111 @@ impl SysT for Sys {
             user,
             hostname,
             is_root,
+            unprivileged: None,
         })
     }
 
-    fn spawn() {}
+    fn spawn(&self, command: &SpawnCommand) -> io::Result<ProcessHandle> {
+        let mut raw = command.to_command();
+
+        if let Some(ref account) = self.unprivileged
+            && command.is_unprivileged()
+        {
+            // std drops the supplementary groups of root along with the uid
+            raw.uid(account.uid).gid(account.gid);
+
+            // the inherited working directory, e.g. `/root`, may be off limits
+            if raw.get_current_dir().is_none() {
+                raw.current_dir("/");
+            }
+        }
+
+        ProcessHandle::spawn(command, raw)
+    }
+
+    fn drop_privileges(&mut self, user: &str, create: bool) -> io::Result<()> {
+        if !self.is_root {
+            return Ok(());
+        }
+
+        let account = match find_account(user)? {
+            Some(account) => account,
+            None if !create => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("the user `{user}` doesn't exist and creating it wasn't requested"),
+                ));
+            }
+            None => {
+                // another process creating it at the same time makes ours fail,
+                // look it up again either way
+                let created = create_account(user);
+
+                match (find_account(user)?, created) {
+                    (Some(account), _) => account,
+                    (None, Err(err)) => return Err(err),
+                    (None, Ok(())) => {
+                        return Err(io::Error::other(format!(
+                            "created the user `{user}`, but can't look it up"
+                        )));
+                    }
+                }
+            }
+        };
+
+        if account.uid == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the user `{}` has root privileges too", account.name),
+            ));
+        }
+
+        self.unprivileged = Some(account);
+
+        Ok(())
+    }
+
+    fn chown(&self, path: &Path) -> io::Result<()> {
+        match self.unprivileged {
+            Some(ref account) => chown_recursive(path, account.uid, account.gid),
+            None => Ok(()),
+        }
+    }
+
+    /// Only the permission bits are looked at, ACLs aren't
+    fn check_access(&self, path: &Path) -> io::Result<()> {
+        let Some(ref account) = self.unprivileged else {
+            return Ok(());
+        };
 
-    fn kill() {}
+        // the lookup needs every directory on the way to be searchable
+        let path = fs::canonicalize(path)?;
+        for dir in path.ancestors() {
+            let metadata = fs::metadata(dir)?;
+            let mode = metadata.mode();
+
+            // the supplementary groups are dropped along with root, see `spawn`
+            let searchable = match (metadata.uid() == account.uid, metadata.gid() == account.gid) {
+                (true, _) => mode & 0o100 != 0,
+                (false, true) => mode & 0o010 != 0,
+                (false, false) => mode & 0o001 != 0,
+            };
+
+            if !searchable {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!(
+                        "the user `{}` can't reach `{}`, `{}` isn't searchable for it",
+                        account.name,
+                        path.display(),
+                        dir.display()
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl SysInfo for Sys {
@@ -81,7 +196,7 @@ impl Sys {
                 return Err(io::Error::last_os_error());
             }
 
-            let host_str = CStr::from_ptr(buffer.as_ptr() as *const i8);
+            let host_str = CStr::from_ptr(buffer.as_ptr());
             let host = host_str
                 .to_str()
                 .ok()
@@ -96,3 +211,129 @@ impl Sys {
         unsafe { libc::geteuid() == 0 }
     }
 }
+
+/// Looks up the user `name` in the password database
+fn find_account(name: &str) -> io::Result<Option<Account>> {
+    let c_name = CString::new(name).map_err(io::Error::other)?;
+    let mut buffer = vec![0 as c_char; 16 * 1024];
+    // SAFETY: `passwd` is plain old data, `getpwnam_r` fills it in
+    let mut pwd: libc::passwd = unsafe { mem::zeroed() };
+    let mut result = ptr::null_mut();
+
+    // SAFETY: all pointers are valid for the duration of the call, the strings
+    // `pwd` points to live in `buffer`
+    let code = unsafe {
+        libc::getpwnam_r(
+            c_name.as_ptr(),
+            &mut pwd,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+
+    if code != 0 {
+        return Err(io::Error::from_raw_os_error(code));
+    }
+
+    if result.is_null() {
+        return Ok(None);
+    }
+
+    Ok(Some(Account {
+        name: name.into(),
+        uid: pwd.pw_uid,
+        gid: pwd.pw_gid,
+    }))
+}
+
+/// Creates a system user without a home directory or login shell, through
+/// `useradd` or, on busybox based systems like alpine, `adduser`
+fn create_account(name: &str) -> io::Result<()> {
+    let programs: [(&str, &[&str]); 2] = [
+        (
+            "useradd",
+            &[
+                "--system",
+                "--user-group",
+                "--no-create-home",
+                "--shell",
+                "/usr/sbin/nologin",
+            ],
+        ),
+        ("adduser", &["-S", "-D", "-H", "-s", "/sbin/nologin"]),
+    ];
+
+    for (program, args) in programs {
+        let output = Command::new(program)
+            .args(args)
+            .arg(name)
+            .stdin(Stdio::null())
+            .output();
+
+        match output {
+            Ok(output) if output.status.success() => return Ok(()),
+            Ok(output) => {
+                return Err(io::Error::other(format!(
+                    "`{program}` exited with {}: {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("the user `{name}` doesn't exist and neither `useradd` nor `adduser` is available"),
+    ))
+}
+
+/// `chown -R`, symlinks are changed themselves and never followed
+fn chown_recursive(path: &Path, uid: u32, gid: u32) -> io::Result<()> {
+    std::os::unix::fs::lchown(path, Some(uid), Some(gid))?;
+
+    if fs::symlink_metadata(path)?.is_dir() {
+        for entry in fs::read_dir(path)? {
+            chown_recursive(&entry?.path(), uid, gid)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub(super) fn send_signal(child: &mut Child, signal: Signal) -> io::Result<()> {
+    // the child is still owned (not yet reaped), so its pid can't be reused
+    signal_pid(child.id(), signal)
+}
+
+/// Delivers `signal` to any process, including ones this process didn't spawn
+pub fn signal_pid(pid: u32, signal: Signal) -> io::Result<()> {
+    let signal = match signal {
+        Signal::Term => libc::SIGTERM,
+        Signal::Int => libc::SIGINT,
+        Signal::Quit => libc::SIGQUIT,
+        Signal::Kill => libc::SIGKILL,
+        Signal::Hup => libc::SIGHUP,
+    };
+
+    // SAFETY: `kill` has no memory safety requirements
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Whether a process with `pid` exists, owned by any user
+pub fn process_exists(pid: u32) -> bool {
+    // signal 0 only runs the existence and permission checks
+    // SAFETY: `kill` has no memory safety requirements
+    if unsafe { libc::kill(pid as libc::pid_t, 0) } == 0 {
+        return true;
+    }
+
+    io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
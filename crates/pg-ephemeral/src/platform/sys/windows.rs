@@ -1,14 +1,18 @@
 use std::os::windows::raw::HANDLE;
+use std::process::Child;
 use std::{io, mem, ptr};
 
+use windows_sys::Win32::Foundation::{CloseHandle, STILL_ACTIVE};
 use windows_sys::Win32::Security::{
-    CreateRestrictedToken, DISABLE_MAX_PRIVILEGE, GetTokenInformation, LUA_TOKEN, TOKEN_DUPLICATE,
-    TOKEN_ELEVATION, TOKEN_QUERY, TokenElevation,
+    GetTokenInformation, TOKEN_DUPLICATE, TOKEN_ELEVATION, TOKEN_QUERY, TokenElevation,
+};
+use windows_sys::Win32::System::Threading::{
+    GetCurrentProcess, GetExitCodeProcess, OpenProcess, OpenProcessToken,
+    PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_TERMINATE, TerminateProcess,
 };
-use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
 use windows_sys::Win32::System::WindowsProgramming::{GetComputerNameA, GetUserNameW};
 
-use super::{SysInfo, SysT};
+use super::{ProcessHandle, Signal, SpawnCommand, SysInfo, SysT};
 
 #[derive(Debug)]
 pub struct Sys {
@@ -26,15 +30,6 @@ pub struct Sys {
 
     /// Whether the current user is running with elevated/administrative privileges
     is_elevated: bool,
-
-    /// Access token used for spawning the PostgreSQL child process
-    /// If [`Sys::is_elevated`] is true, this will be a restricted token.
-    /// Otherwise, this will reuse [`Sys::parent_token`]
-    access_token: Option<HANDLE>,
-
-    /// Handle to the spawned PostgreSQL child process, if any
-    /// Only a single child process is managed at a time
-    child: Option<HANDLE>,
 }
 
 impl SysT for Sys {
@@ -52,26 +47,31 @@ impl SysT for Sys {
         let token_handle = Self::current_process_token(process_handle)?;
         let is_elevated = Self::is_elevated(token_handle)?;
 
-        let access_token = if is_elevated {
-            Some(Self::create_restricted_token(token_handle)?)
-        } else {
-            None
-        };
-
         Ok(Self {
             user: Self::get_username()?,
             sysname: Self::get_sysname()?,
             parent_token: token_handle,
-            access_token,
             process_handle,
             is_elevated,
-            child: None,
         })
     }
 
-    fn spawn() {}
+    /// `initdb` and `postgres` refuse to run with administrative privileges.
+    /// `std::process` can't hand them a restricted token, so spawning them from
+    /// an elevated process fails up front instead of with their own error
+    fn spawn(&self, command: &SpawnCommand) -> io::Result<ProcessHandle> {
+        if self.is_elevated && command.is_unprivileged() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "`{}` can't run with administrative privileges, start it from a non-elevated process",
+                    command.program_name()
+                ),
+            ));
+        }
 
-    fn kill() {}
+        ProcessHandle::spawn(command, command.to_command())
+    }
 }
 
 impl SysInfo for Sys {
@@ -132,33 +132,6 @@ impl Sys {
         Ok(token)
     }
 
-    fn create_restricted_token(token_handle: HANDLE) -> io::Result<HANDLE> {
-        let mut new_token: HANDLE = ptr::null_mut();
-
-        unsafe {
-            if CreateRestrictedToken(
-                token_handle,
-                DISABLE_MAX_PRIVILEGE | LUA_TOKEN,
-                0,
-                ptr::null(),
-                0,
-                ptr::null(),
-                0,
-                ptr::null(),
-                &mut new_token,
-            ) == 0
-            {
-                return Err(io::Error::last_os_error());
-            }
-        }
-
-        if new_token.is_null() {
-            return Err(io::Error::other("new token handle is not valid"));
-        }
-
-        Ok(new_token)
-    }
-
     fn is_elevated(token_handle: HANDLE) -> io::Result<bool> {
         let mut elevation = TOKEN_ELEVATION::default();
         let mut size = 0;
@@ -179,3 +152,59 @@ impl Sys {
         Ok(elevation.TokenIsElevated != 0)
     }
 }
+
+pub(super) fn send_signal(child: &mut Child, signal: Signal) -> io::Result<()> {
+    match signal {
+        Signal::Kill => child.kill(),
+        // windows has no signals, postgres emulates them through a named pipe
+        // that only `pg_ctl` knows how to talk to
+        _ => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "only `Signal::Kill` can be delivered on windows",
+        )),
+    }
+}
+
+/// Delivers `signal` to any process, including ones this process didn't spawn.
+/// Same as [`send_signal`], only [`Signal::Kill`] is supported
+pub fn signal_pid(pid: u32, signal: Signal) -> io::Result<()> {
+    if signal != Signal::Kill {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "only `Signal::Kill` can be delivered on windows",
+        ));
+    }
+
+    unsafe {
+        let process = OpenProcess(PROCESS_TERMINATE, 0, pid);
+        if process.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        let result = TerminateProcess(process, 1);
+        CloseHandle(process);
+
+        if result == 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a process with `pid` exists and didn't exit yet
+pub fn process_exists(pid: u32) -> bool {
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            // access denied still means the process is there
+            return io::Error::last_os_error().kind() == io::ErrorKind::PermissionDenied;
+        }
+
+        let mut exit_code = 0;
+        let result = GetExitCodeProcess(process, &mut exit_code);
+        CloseHandle(process);
+
+        result != 0 && exit_code == STILL_ACTIVE as u32
+    }
+}
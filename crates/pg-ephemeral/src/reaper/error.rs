@@ -0,0 +1,15 @@
+#[derive(Debug, thiserror::Error)]
+pub enum ReaperError {
+    #[error("I/O operation failed: {0}")]
+    IOError(#[from] std::io::Error),
+
+    #[cfg(feature = "containerized")]
+    #[error("failed to connect to docker: {0}")]
+    DockerClientError(#[from] testcontainers::core::client::ClientError),
+
+    #[cfg(feature = "containerized")]
+    #[error("docker request failed: {0}")]
+    DockerError(#[from] testcontainers::bollard::errors::Error),
+}
+
+pub type ReaperResult<T> = std::result::Result<T, ReaperError>;
@@ -0,0 +1,348 @@
+use std::path::{Path, PathBuf};
+
+use super::error::{ReaperError, ReaperResult};
+use crate::common::session::session_id;
+use crate::platform::sys::process_exists;
+
+/// A resource left behind by a process that no longer exists
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReapedResource {
+    /// `pgtemp-` directory of a [`crate::local::Local`] instance
+    DataDir {
+        path: PathBuf,
+        owner_pid: u32,
+        /// Orphaned postmaster still serving the directory, stopped before removal
+        postmaster_pid: Option<u32>,
+    },
+
+    /// Container of a [`crate::containerized::Containerized`] instance
+    Container {
+        id: String,
+        name: Option<String>,
+        owner_pid: u32,
+    },
+
+    /// Container kept by [`crate::containerized::ContainerizedBuilder::with_reuse`],
+    /// only collected with [`Reaper::with_reused`]
+    ReusedContainer { id: String, name: Option<String> },
+}
+
+/// Outcome of a sweep
+#[derive(Debug, Default)]
+pub struct ReapReport {
+    /// Stale resources, removed unless [`Reaper::with_dry_run`] is set
+    pub reaped: Vec<ReapedResource>,
+    /// Stale resources that could not be removed, the sweep went on without them
+    pub failed: Vec<(ReapedResource, ReaperError)>,
+}
+
+/// Garbage-collects resources of processes that died without cleaning up,
+/// e.g. test binaries killed with `SIGKILL`.
+///
+/// Every resource records the pid and a random session id of its creator,
+/// a resource is stale once no process with that pid exists anymore. The
+/// current process' own resources are never touched, neither are persisted
+/// data directories (see [`crate::local::LocalBuilder::keep`]). A docker
+/// daemon may be shared by several machines, only containers labelled with
+/// the hostname and machine id of this one are considered. Those of an
+/// earlier boot are stale regardless of their pid.
+#[derive(Debug, Clone)]
+pub struct Reaper {
+    dry_run: bool,
+    reused: bool,
+    temp_dir: PathBuf,
+}
+
+impl Default for Reaper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reaper {
+    pub fn new() -> Self {
+        Self {
+            dry_run: false,
+            reused: false,
+            temp_dir: std::env::temp_dir(),
+        }
+    }
+
+    /// Only report stale resources, without removing them
+    #[inline]
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Also remove the reused containers of this machine, which no process
+    /// owns and which are otherwise kept forever. The next run recreates its
+    /// container, a process attached to one right now loses its server
+    #[inline]
+    pub fn with_reused(mut self, reused: bool) -> Self {
+        self.reused = reused;
+        self
+    }
+
+    /// Directory searched for data directories, the system temp dir by default
+    #[inline]
+    pub fn with_temp_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.temp_dir = dir.as_ref().to_path_buf();
+        self
+    }
+
+    /// Whether a resource created by `pid` in `session` is stale
+    fn is_orphaned(pid: u32, session: &str) -> bool {
+        session != session_id() && pid != std::process::id() && !process_exists(pid)
+    }
+}
+
+#[cfg(feature = "local")]
+mod local {
+    use std::fs;
+    use std::path::Path;
+    use std::time::Instant;
+
+    use super::{ReapReport, ReapedResource, Reaper, ReaperResult};
+    use crate::common::constants::{
+        DEFAULT_SHUTDOWN_TIMEOUT, LOCAL_DATA_DIR, LOCAL_OWNER_FILE, LOCAL_POLL_INTERVAL,
+        LOCAL_TMP_DIR_PREFIX,
+    };
+    use crate::platform::sys::{Signal, process_exists, signal_pid};
+    use crate::{log_debug, log_info, log_warn};
+
+    impl Reaper {
+        /// Stops orphaned postmasters and removes the `pgtemp-` directories of
+        /// dead [`crate::local::Local`] owners
+        pub fn reap_data_dirs(&self) -> ReaperResult<ReapReport> {
+            let mut report = ReapReport::default();
+
+            for entry in fs::read_dir(&self.temp_dir)? {
+                let entry = entry?;
+                let path = entry.path();
+
+                let is_temp_dir = entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(LOCAL_TMP_DIR_PREFIX)
+                    && entry.file_type()?.is_dir();
+                if !is_temp_dir {
+                    continue;
+                }
+
+                // persisted, still being created, or not ours at all
+                let Some((owner_pid, session)) = read_owner(&path) else {
+                    continue;
+                };
+
+                if !Self::is_orphaned(owner_pid, &session) {
+                    continue;
+                }
+
+                let data_dir = path.join(LOCAL_DATA_DIR);
+                let postmaster_pid = read_postmaster_pid(&data_dir)
+                    .filter(|pid| process_exists(*pid) && is_postmaster_of(*pid, &data_dir));
+
+                let resource = ReapedResource::DataDir {
+                    path: path.clone(),
+                    owner_pid,
+                    postmaster_pid,
+                };
+
+                if self.dry_run {
+                    log_debug!(path = %path.display(), owner_pid, "found an orphaned data directory");
+                    report.reaped.push(resource);
+                    continue;
+                }
+
+                if let Some(pid) = postmaster_pid
+                    && let Err(err) = stop_orphan(pid)
+                {
+                    log_warn!(error = %err, pid, "failed to stop an orphaned postmaster");
+                    report.failed.push((resource, err.into()));
+                    continue;
+                }
+
+                // e.g. files of another user, the remaining directories may still go
+                match fs::remove_dir_all(&path) {
+                    Ok(()) => {
+                        log_info!(path = %path.display(), owner_pid, "removed an orphaned data directory");
+                        report.reaped.push(resource);
+                    }
+                    Err(err) => {
+                        log_warn!(error = %err, path = %path.display(), "failed to remove an orphaned data directory");
+                        report.failed.push((resource, err.into()));
+                    }
+                }
+            }
+
+            Ok(report)
+        }
+    }
+
+    /// `(pid, session)` from the marker written by [`crate::local::LocalBuilder::build`]
+    fn read_owner(temp_dir: &Path) -> Option<(u32, String)> {
+        let content = fs::read_to_string(temp_dir.join(LOCAL_OWNER_FILE)).ok()?;
+        let mut lines = content.lines();
+
+        let pid = lines.next()?.trim().parse().ok()?;
+        let session = lines.next()?.trim().to_string();
+
+        Some((pid, session))
+    }
+
+    /// First line of `postmaster.pid`
+    fn read_postmaster_pid(data_dir: &Path) -> Option<u32> {
+        let content = fs::read_to_string(data_dir.join("postmaster.pid")).ok()?;
+        content.lines().next()?.trim().parse().ok()
+    }
+
+    /// Guards against a recycled pid, the postmaster runs inside its data directory
+    #[cfg(target_os = "linux")]
+    fn is_postmaster_of(pid: u32, data_dir: &Path) -> bool {
+        let cwd = fs::read_link(format!("/proc/{pid}/cwd"));
+        let data_dir = fs::canonicalize(data_dir);
+
+        matches!((cwd, data_dir), (Ok(cwd), Ok(data_dir)) if cwd == data_dir)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn is_postmaster_of(_pid: u32, _data_dir: &Path) -> bool {
+        true
+    }
+
+    /// Immediate shutdown, nobody is going to use the data anymore
+    fn stop_orphan(pid: u32) -> std::io::Result<()> {
+        match signal_pid(pid, Signal::Quit) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::Unsupported => {
+                return signal_pid(pid, Signal::Kill);
+            }
+            Err(err) => return Err(err),
+        }
+
+        let deadline = Instant::now() + DEFAULT_SHUTDOWN_TIMEOUT;
+        while process_exists(pid) {
+            if Instant::now() >= deadline {
+                return signal_pid(pid, Signal::Kill);
+            }
+
+            std::thread::sleep(LOCAL_POLL_INTERVAL);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "containerized")]
+mod containerized {
+    use std::collections::HashMap;
+
+    use testcontainers::bollard::query_parameters::{
+        ListContainersOptions, RemoveContainerOptions,
+    };
+    use testcontainers::core::client::docker_client_instance;
+
+    use super::{ReapReport, ReapedResource, Reaper, ReaperResult};
+    use crate::common::constants::{
+        CONTAINERIZED_LABEL_BOOT, CONTAINERIZED_LABEL_FINGERPRINT, CONTAINERIZED_LABEL_HOST,
+        CONTAINERIZED_LABEL_MANAGED, CONTAINERIZED_LABEL_PID, CONTAINERIZED_LABEL_SESSION,
+    };
+    use crate::common::session::{boot_id, host_id};
+    use crate::{log_debug, log_info, log_warn};
+
+    impl Reaper {
+        /// Removes the containers of dead [`crate::containerized::Containerized`]
+        /// owners, found through their labels
+        pub async fn reap_containers(&self) -> ReaperResult<ReapReport> {
+            let docker = docker_client_instance().await?;
+
+            let options = ListContainersOptions {
+                all: true,
+                filters: Some(HashMap::from([(
+                    "label".to_string(),
+                    vec![format!("{CONTAINERIZED_LABEL_MANAGED}=true")],
+                )])),
+                ..Default::default()
+            };
+
+            let mut report = ReapReport::default();
+
+            for container in docker.list_containers(Some(options)).await? {
+                let (Some(id), Some(labels)) = (container.id, container.labels) else {
+                    continue;
+                };
+
+                // pids of other machines say nothing about processes here
+                if labels.get(CONTAINERIZED_LABEL_HOST).map(String::as_str) != Some(host_id()) {
+                    continue;
+                }
+
+                let name = container
+                    .names
+                    .and_then(|names| names.into_iter().next())
+                    .map(|name| name.trim_start_matches('/').to_string());
+
+                let owner_pid = labels
+                    .get(CONTAINERIZED_LABEL_PID)
+                    .and_then(|pid| pid.parse().ok());
+
+                let resource = match owner_pid {
+                    Some(owner_pid) => {
+                        let session = labels
+                            .get(CONTAINERIZED_LABEL_SESSION)
+                            .map(String::as_str)
+                            .unwrap_or_default();
+
+                        let earlier_boot = match (labels.get(CONTAINERIZED_LABEL_BOOT), boot_id()) {
+                            (Some(label), Some(boot_id)) => label != boot_id,
+                            _ => false,
+                        };
+
+                        if !earlier_boot && !Self::is_orphaned(owner_pid, session) {
+                            continue;
+                        }
+
+                        ReapedResource::Container {
+                            id: id.clone(),
+                            name,
+                            owner_pid,
+                        }
+                    }
+                    // kept on purpose, see `ContainerizedBuilder::with_reuse`
+                    None if self.reused && labels.contains_key(CONTAINERIZED_LABEL_FINGERPRINT) => {
+                        ReapedResource::ReusedContainer {
+                            id: id.clone(),
+                            name,
+                        }
+                    }
+                    None => continue,
+                };
+
+                if self.dry_run {
+                    log_debug!(id = %id, "found an orphaned container");
+                    report.reaped.push(resource);
+                    continue;
+                }
+
+                let options = RemoveContainerOptions {
+                    force: true,
+                    v: true,
+                    ..Default::default()
+                };
+                match docker.remove_container(&id, Some(options)).await {
+                    Ok(()) => {
+                        log_info!(id = %id, "removed an orphaned container");
+                        report.reaped.push(resource);
+                    }
+                    Err(err) => {
+                        log_warn!(error = %err, id = %id, "failed to remove an orphaned container");
+                        report.failed.push((resource, err.into()));
+                    }
+                }
+            }
+
+            Ok(report)
+        }
+    }
+}
@@ -0,0 +1,5 @@
+mod error;
+mod impls;
+
+pub use error::{ReaperError, ReaperResult};
+pub use impls::{ReapReport, ReapedResource, Reaper};
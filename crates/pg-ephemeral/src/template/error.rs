@@ -0,0 +1,15 @@
+use crate::client::ClientError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("invalid database name `{0}`: must be 1 to 63 bytes")]
+    InvalidName(String),
+
+    #[error("SQL execution failed: {0}")]
+    ClientError(#[from] ClientError),
+
+    #[error("template setup failed: {0}")]
+    SetupFailed(Box<dyn std::error::Error + Send + Sync>),
+}
+
+pub type TemplateResult<T> = std::result::Result<T, TemplateError>;
@@ -0,0 +1,194 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::error::{TemplateError, TemplateResult};
+use crate::ConnectionInfo;
+use crate::client::Client;
+use crate::common::constants::PG_MAX_IDENT_LEN;
+use crate::common::sql::{quote_ident, quote_literal};
+use crate::{log_debug, log_error};
+
+static DATABASE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A database prepared once (migrations, seed data) and then cloned into a
+/// fresh database per test with `CREATE DATABASE ... TEMPLATE`.
+///
+/// Cloning copies the files of the template, which is a lot cheaper than
+/// starting a server or re-running migrations for every test. The SQL runs
+/// through the built-in [`Client`], with either backend and without `psql`
+/// on the host.
+#[derive(Debug)]
+pub struct DatabaseTemplate {
+    /// Connection to the instance, used for `CREATE`/`DROP DATABASE`
+    admin: ConnectionInfo,
+    name: String,
+}
+
+impl DatabaseTemplate {
+    /// Creates the template database `name` on the server behind `admin` and
+    /// runs `setup` against it once.
+    ///
+    /// A database left over with the same name is replaced. Once `setup`
+    /// returns, connections to the template are disallowed, since cloning
+    /// fails while anyone is connected to it.
+    pub fn prepare<F, E>(admin: ConnectionInfo, name: &str, setup: F) -> TemplateResult<Self>
+    where
+        F: FnOnce(&ConnectionInfo) -> Result<(), E>,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        validate_name(name)?;
+
+        let template = Self {
+            admin,
+            name: name.into(),
+        };
+
+        let mut client = Client::connect(&template.admin)?;
+        drop_database(&mut client, name)?;
+        client.execute(&format!("CREATE DATABASE {}", quote_ident(name)))?;
+
+        log_debug!(template = %name, "running the template setup");
+
+        if let Err(err) = setup(&template.connection_info()) {
+            let _ = drop_database(&mut client, name);
+            return Err(TemplateError::SetupFailed(err.into()));
+        }
+
+        terminate_connections(&mut client, name)?;
+        client.execute(&format!(
+            "ALTER DATABASE {} WITH IS_TEMPLATE true ALLOW_CONNECTIONS false",
+            quote_ident(name)
+        ))?;
+
+        Ok(template)
+    }
+
+    /// Same as [`DatabaseTemplate::prepare`], with `sql` as the setup
+    pub fn prepare_sql(admin: ConnectionInfo, name: &str, sql: &str) -> TemplateResult<Self> {
+        Self::prepare(admin, name, |info| Client::connect(info)?.execute(sql))
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Connection to the template itself, only usable during the setup
+    pub fn connection_info(&self) -> ConnectionInfo {
+        ConnectionInfo {
+            database: self.name.clone(),
+            ..self.admin.clone()
+        }
+    }
+
+    /// Clones the template into a new, uniquely named database. The database
+    /// is dropped again together with the returned handle
+    pub fn create_database(&self) -> TemplateResult<TemplateDatabase> {
+        let suffix = format!(
+            "_{}_{}",
+            std::process::id(),
+            DATABASE_COUNTER.fetch_add(1, Ordering::SeqCst)
+        );
+
+        let mut prefix_len = PG_MAX_IDENT_LEN
+            .saturating_sub(suffix.len())
+            .min(self.name.len());
+        while !self.name.is_char_boundary(prefix_len) {
+            prefix_len -= 1;
+        }
+
+        let name = format!("{}{}", &self.name[..prefix_len], suffix);
+
+        Client::connect(&self.admin)?.execute(&format!(
+            "CREATE DATABASE {} TEMPLATE {}",
+            quote_ident(&name),
+            quote_ident(&self.name)
+        ))?;
+
+        Ok(TemplateDatabase {
+            info: ConnectionInfo {
+                database: name,
+                ..self.admin.clone()
+            },
+            admin: self.admin.clone(),
+        })
+    }
+}
+
+/// A database cloned from a [`DatabaseTemplate`], dropped when this handle goes away
+#[derive(Debug)]
+pub struct TemplateDatabase {
+    info: ConnectionInfo,
+    admin: ConnectionInfo,
+}
+
+impl TemplateDatabase {
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.info.database
+    }
+
+    #[inline]
+    pub fn connection_info(&self) -> &ConnectionInfo {
+        &self.info
+    }
+}
+
+impl Drop for TemplateDatabase {
+    fn drop(&mut self) {
+        let dropped = Client::connect(&self.admin)
+            .map_err(TemplateError::from)
+            .and_then(|mut client| drop_database(&mut client, &self.info.database));
+
+        if let Err(_err) = dropped {
+            log_error!(error = %_err, database = %self.info.database, "failed to drop the database");
+        }
+    }
+}
+
+fn validate_name(name: &str) -> TemplateResult<()> {
+    if name.is_empty() || name.len() > PG_MAX_IDENT_LEN {
+        return Err(TemplateError::InvalidName(name.into()));
+    }
+
+    Ok(())
+}
+
+/// Kicks out everyone still connected to `database`, leftover pools of a test would
+/// otherwise block dropping or cloning it
+fn terminate_connections(client: &mut Client, database: &str) -> TemplateResult<()> {
+    client.execute(&format!(
+        "SELECT pg_terminate_backend(pid) FROM pg_stat_activity \
+         WHERE datname = {} AND pid <> pg_backend_pid()",
+        quote_literal(database)
+    ))?;
+
+    Ok(())
+}
+
+/// Drops `database` if it exists, including template databases
+fn drop_database(client: &mut Client, database: &str) -> TemplateResult<()> {
+    let exists = client.query_scalar(&format!(
+        "SELECT 1 FROM pg_database WHERE datname = {}",
+        quote_literal(database)
+    ))?;
+
+    if exists.is_none() {
+        return Ok(());
+    }
+
+    // templates can't be dropped
+    client.execute(&format!(
+        "ALTER DATABASE {} WITH IS_TEMPLATE false",
+        quote_ident(database)
+    ))?;
+
+    terminate_connections(client, database)?;
+
+    // `DROP DATABASE` can't run inside the implicit transaction of a multi statement query
+    client.execute(&format!(
+        "DROP DATABASE IF EXISTS {}",
+        quote_ident(database)
+    ))?;
+
+    Ok(())
+}
@@ -0,0 +1,5 @@
+mod error;
+mod impls;
+
+pub use error::{TemplateError, TemplateResult};
+pub use impls::{DatabaseTemplate, TemplateDatabase};